@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::columns::{LifeState, TeamName};
+use crate::tracer::{SnapshotView, States, Tracer, WithTick};
+
+/// degrees per second
+pub const SNAP_THRESHOLD: f32 = 1000.0;
+/// ticks
+pub const SNAP_WINDOW: u32 = 8;
+/// half the width of a player hull, in hammer units
+pub const HITBOX_RADIUS: f32 = 24.0;
+/// height of the chest as a fraction of eye height
+const CHEST_HEIGHT: f32 = 0.7;
+/// views further apart than this aren't differenced, the player was dead or
+/// out of view in between
+const MAX_SAMPLE_GAP_S: f32 = 0.5;
+
+pub struct AimConfig {
+    /// angular velocity above which a view change counts as a snap
+    pub snap_threshold: f32,
+    /// how many ticks after a snap the view may land on an enemy
+    pub window: u32,
+    /// how close the view ray has to pass to the head or chest
+    pub hitbox_radius: f32,
+}
+
+impl Default for AimConfig {
+    fn default() -> Self {
+        Self {
+            snap_threshold: SNAP_THRESHOLD,
+            window: SNAP_WINDOW,
+            hitbox_radius: HITBOX_RADIUS,
+        }
+    }
+}
+
+pub type Vec3 = [f32; 3];
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm(a: Vec3) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// unit vector along a view, following the source convention of positive
+/// pitch looking down
+pub fn view_direction(pitch: f32, yaw: f32) -> Vec3 {
+    let (pitch, yaw) = (pitch.to_radians(), yaw.to_radians());
+    [
+        pitch.cos() * yaw.cos(),
+        pitch.cos() * yaw.sin(),
+        -pitch.sin(),
+    ]
+}
+
+/// angle between two directions in degrees
+pub fn angle_between(a: Vec3, b: Vec3) -> f32 {
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        return 0.0;
+    }
+    (dot(a, b) / norms).clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+pub fn eye_position(state: SnapshotView) -> Vec3 {
+    [*state.position_x, *state.position_y, *state.eye_z]
+}
+
+pub fn chest_position(state: SnapshotView) -> Vec3 {
+    [
+        *state.position_x,
+        *state.position_y,
+        state.position_z + state.eye_height * CHEST_HEIGHT,
+    ]
+}
+
+pub fn is_enemy(of: SnapshotView, other: SnapshotView) -> bool {
+    *other.state == LifeState::Alive
+        && matches!(other.team, TeamName::Red | TeamName::Blu)
+        && other.team != of.team
+}
+
+/// The latest traced state of every player at any tick, as rows of the
+/// traced columns. Players are told apart by roster key, as user ids are
+/// handed out again after a disconnect.
+pub struct Timeline<'a> {
+    states: &'a States,
+    /// rows of each roster entry, in tick order
+    players: HashMap<u32, Vec<usize>>,
+}
+
+impl<'a> Timeline<'a> {
+    pub fn new(states: &'a States) -> Self {
+        let mut players: HashMap<u32, Vec<usize>> = HashMap::new();
+        for index in 0..states.len() {
+            if let Some(key) = *states.view(index).roster_key {
+                players.entry(key).or_default().push(index);
+            }
+        }
+        Self { states, players }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = u32> + '_ {
+        self.players.keys().copied()
+    }
+
+    /// the user id of roster entry `key`, which doesn't change over its rows
+    pub fn user_id(&self, key: u32) -> Option<u16> {
+        let row = *self.players.get(&key)?.first()?;
+        *self.states.view(row).user_id
+    }
+
+    /// every traced state of `key` with its tick
+    pub fn states(&self, key: u32) -> impl Iterator<Item = (u32, SnapshotView<'a>)> + '_ {
+        let rows = self.players.get(&key).map(Vec::as_slice);
+        let ticks = self.states.ticks();
+        rows.unwrap_or_default()
+            .iter()
+            .map(|&index| (ticks[index], self.states.view(index)))
+    }
+
+    pub fn at(&self, key: u32, tick: u32) -> Option<SnapshotView<'a>> {
+        let rows = self.players.get(&key)?;
+        let ticks = self.states.ticks();
+        let index = rows.partition_point(|&row| ticks[row] <= tick);
+        let row = rows[index.checked_sub(1)?];
+        Some(self.states.view(row))
+    }
+
+    /// enemies of `key` that are alive at `tick`
+    pub fn enemies(&self, key: u32, tick: u32) -> Vec<(u32, SnapshotView<'a>)> {
+        let Some(player) = self.at(key, tick) else {
+            return Vec::new();
+        };
+        self.keys()
+            .filter_map(|other| Some((other, self.at(other, tick)?)))
+            .filter(|(_, other)| is_enemy(player, *other))
+            .collect()
+    }
+}
+
+/// Where an enemy is relative to a player's view ray.
+pub struct Target {
+    pub key: u32,
+    pub user_id: Option<u16>,
+    pub head_offset: f32,
+    pub chest_offset: f32,
+    pub distance: f32,
+}
+
+impl Target {
+    fn new(key: u32, enemy: SnapshotView, eye: Vec3, forward: Vec3) -> Self {
+        let head = sub(eye_position(enemy), eye);
+        let chest = sub(chest_position(enemy), eye);
+        Self {
+            key,
+            user_id: *enemy.user_id,
+            head_offset: angle_between(forward, head),
+            chest_offset: angle_between(forward, chest),
+            distance: norm(chest),
+        }
+    }
+
+    pub fn offset(&self) -> f32 {
+        self.head_offset.min(self.chest_offset)
+    }
+
+    /// whether the view ray passes within `radius` units of the head or chest
+    pub fn on_hitbox(&self, radius: f32) -> bool {
+        self.offset() <= (radius / self.distance.max(1.0)).atan().to_degrees()
+    }
+}
+
+/// where `enemy` is relative to the view ray of `key` looking along `pitch`
+/// and `yaw` at `tick`
+pub fn aim_at(
+    timeline: &Timeline,
+    key: u32,
+    enemy: u32,
+    tick: u32,
+    pitch: f32,
+    yaw: f32,
+) -> Option<Target> {
+    let eye = eye_position(timeline.at(key, tick)?);
+    let state = timeline.at(enemy, tick)?;
+    Some(Target::new(enemy, state, eye, view_direction(pitch, yaw)))
+}
+
+/// the enemy closest to the view ray of `key` looking along `pitch` and
+/// `yaw` at `tick`
+pub fn closest_enemy(
+    timeline: &Timeline,
+    key: u32,
+    tick: u32,
+    pitch: f32,
+    yaw: f32,
+) -> Option<Target> {
+    let eye = eye_position(timeline.at(key, tick)?);
+    let forward = view_direction(pitch, yaw);
+    timeline
+        .enemies(key, tick)
+        .into_iter()
+        .map(|(enemy, state)| Target::new(enemy, state, eye, forward))
+        .min_by(|a, b| a.offset().total_cmp(&b.offset()))
+}
+
+#[derive(Clone, Copy)]
+pub struct ViewSample {
+    pub tick: u32,
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+/// whether we have full precision user commands for roster entry `key`,
+/// i.e. they recorded the demo
+pub fn has_usercmds(tracer: &Tracer, key: u32) -> bool {
+    !tracer.usercmds.is_empty() && tracer.pov_key == Some(key)
+}
+
+/// the view of `key` once per tick, from user commands when we have them
+pub fn view_samples(tracer: &Tracer, timeline: &Timeline, key: u32) -> Vec<ViewSample> {
+    let samples: Vec<ViewSample> = if has_usercmds(tracer, key) {
+        tracer
+            .usercmds
+            .iter()
+            .map(|cmd| ViewSample {
+                tick: cmd.tick,
+                pitch: cmd.inner.pitch,
+                yaw: cmd.inner.yaw,
+            })
+            .collect()
+    } else {
+        timeline
+            .states(key)
+            .map(|(tick, state)| ViewSample {
+                tick,
+                pitch: *state.pitch_angle,
+                yaw: *state.view_angle,
+            })
+            .collect()
+    };
+    let mut deduped: Vec<ViewSample> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        match deduped.last_mut() {
+            Some(last) if last.tick == sample.tick => *last = sample,
+            _ => deduped.push(sample),
+        }
+    }
+    deduped
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AimSample {
+    pub user_id: Option<u16>,
+    pub roster_key: u32,
+    pub from_usercmd: bool,
+    pub pitch: f32,
+    pub yaw: f32,
+    /// `None` on the first view and after a gap, as are acceleration and
+    /// jerk until there are enough views to difference
+    pub angular_velocity: Option<f32>,
+    pub angular_acceleration: Option<f32>,
+    pub angular_jerk: Option<f32>,
+    pub snap: bool,
+    pub snap_target: Option<u16>,
+    pub snap_target_key: Option<u32>,
+    pub snap_landed_tick: Option<u32>,
+}
+
+/// Angular velocity, acceleration and jerk of every player's view, flagging
+/// snaps that land on an enemy's hitbox shortly after.
+pub fn aim_features(tracer: &Tracer, config: &AimConfig) -> Vec<WithTick<AimSample>> {
+    let timeline = Timeline::new(&tracer.states);
+    let mut features = Vec::new();
+    for key in timeline.keys() {
+        let user_id = timeline.user_id(key);
+        let samples = view_samples(tracer, &timeline, key);
+        let from_usercmd = has_usercmds(tracer, key);
+        let (mut velocity, mut acceleration) = (None, None);
+        for (index, sample) in samples.iter().enumerate() {
+            let (mut next_velocity, mut next_acceleration, mut jerk) = (None, None, None);
+            if let Some(prev) = index.checked_sub(1).map(|prev| samples[prev]) {
+                let dt = tracer.clock.elapsed(prev.tick, sample.tick);
+                if dt > 0.0 && dt <= MAX_SAMPLE_GAP_S {
+                    let turned = angle_between(
+                        view_direction(prev.pitch, prev.yaw),
+                        view_direction(sample.pitch, sample.yaw),
+                    );
+                    next_velocity = Some(turned / dt);
+                    next_acceleration = next_velocity
+                        .zip(velocity)
+                        .map(|(next, velocity)| (next - velocity) / dt);
+                    jerk = next_acceleration
+                        .zip(acceleration)
+                        .map(|(next, acceleration)| (next - acceleration) / dt);
+                }
+            }
+            velocity = next_velocity;
+            acceleration = next_acceleration;
+
+            let snap = velocity.is_some_and(|velocity| velocity >= config.snap_threshold);
+            let landed = if snap {
+                samples[index..]
+                    .iter()
+                    .take_while(|later| later.tick <= sample.tick + config.window)
+                    .find_map(|later| {
+                        closest_enemy(&timeline, key, later.tick, later.pitch, later.yaw)
+                            .filter(|target| target.on_hitbox(config.hitbox_radius))
+                            .map(|target| (later.tick, target))
+                    })
+            } else {
+                None
+            };
+            let inner = AimSample {
+                user_id,
+                roster_key: key,
+                from_usercmd,
+                pitch: sample.pitch,
+                yaw: sample.yaw,
+                angular_velocity: velocity,
+                angular_acceleration: acceleration,
+                angular_jerk: jerk,
+                snap,
+                snap_target: landed.as_ref().and_then(|(_, target)| target.user_id),
+                snap_target_key: landed.as_ref().map(|(_, target)| target.key),
+                snap_landed_tick: landed.as_ref().map(|(tick, _)| *tick),
+            };
+            let tick = sample.tick;
+            features.push(WithTick { inner, tick });
+        }
+    }
+    features.sort_by_key(|feature| feature.tick);
+    features
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CrosshairSample {
+    pub user_id: Option<u16>,
+    pub roster_key: u32,
+    pub target: Option<u16>,
+    pub target_key: Option<u32>,
+    pub head_offset: Option<f32>,
+    pub chest_offset: Option<f32>,
+    pub distance: Option<f32>,
+    pub on_target: bool,
+    pub on_target_since: Option<u32>,
+}
+
+/// The enemy closest to every player's view ray at each tick, with the
+/// angular offset to their head and chest and the tick the crosshair has
+/// been continuously on them since.
+///
+/// There's no culling, every living enemy is measured against every view
+/// sample, which is O(samples × players) lookups per player and so
+/// O(ticks × players²) overall.
+pub fn crosshair(tracer: &Tracer, hitbox_radius: f32) -> Vec<WithTick<CrosshairSample>> {
+    let timeline = Timeline::new(&tracer.states);
+    let mut samples = Vec::new();
+    for key in timeline.keys() {
+        let user_id = timeline.user_id(key);
+        let mut on_target_since: Option<(u32, u32)> = None;
+        for view in view_samples(tracer, &timeline, key) {
+            let target = closest_enemy(&timeline, key, view.tick, view.pitch, view.yaw);
+            let on_target = target
+                .as_ref()
+                .filter(|target| target.on_hitbox(hitbox_radius))
+                .map(|target| target.key);
+            on_target_since = match (on_target, on_target_since) {
+                (Some(target), Some((since_target, since))) if target == since_target => {
+                    Some((target, since))
+                }
+                (Some(target), _) => Some((target, view.tick)),
+                (None, _) => None,
+            };
+            let inner = CrosshairSample {
+                user_id,
+                roster_key: key,
+                target: target.as_ref().and_then(|target| target.user_id),
+                target_key: target.as_ref().map(|target| target.key),
+                head_offset: target.as_ref().map(|target| target.head_offset),
+                chest_offset: target.as_ref().map(|target| target.chest_offset),
+                distance: target.as_ref().map(|target| target.distance),
+                on_target: on_target.is_some(),
+                on_target_since: on_target_since.map(|(_, since)| since),
+            };
+            let tick = view.tick;
+            samples.push(WithTick { inner, tick });
+        }
+    }
+    samples.sort_by_key(|sample| sample.tick);
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{profile, snapshot};
+    use tf_demo_parser::demo::parser::gamestateanalyser::Team;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    #[test]
+    fn view_direction_follows_source_angles() {
+        assert!(close(view_direction(0.0, 0.0), [1.0, 0.0, 0.0]));
+        assert!(close(view_direction(0.0, 90.0), [0.0, 1.0, 0.0]));
+        // positive pitch looks down
+        assert!(close(view_direction(90.0, 0.0), [0.0, 0.0, -1.0]));
+        assert!((norm(view_direction(-30.0, 217.0)) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn angle_between_directions() {
+        assert!((angle_between([1.0, 0.0, 0.0], [0.0, 2.0, 0.0]) - 90.0).abs() < 1e-4);
+        assert!((angle_between([1.0, 0.0, 0.0], [-3.0, 0.0, 0.0]) - 180.0).abs() < 1e-4);
+        assert_eq!(angle_between([1.0, 1.0, 0.0], [2.0, 2.0, 0.0]), 0.0);
+        assert_eq!(angle_between([0.0; 3], [1.0, 0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn on_hitbox_shrinks_with_distance() {
+        let target = |offset: f32, distance: f32| Target {
+            key: 2,
+            user_id: Some(2),
+            head_offset: offset,
+            chest_offset: 45.0,
+            distance,
+        };
+        // 24 units at 240 away is 5.7 degrees
+        assert!(target(5.0, 240.0).on_hitbox(24.0));
+        assert!(!target(6.0, 240.0).on_hitbox(24.0));
+        assert!(!target(5.0, 2400.0).on_hitbox(24.0));
+        // point blank counts as a unit away rather than dividing by zero
+        assert!(target(80.0, 0.0).on_hitbox(24.0));
+    }
+
+    /// a red player turning onto a blu player 240 units east of them, then
+    /// after a gap turning away again
+    fn snap_tracer() -> Tracer {
+        let mut tracer = Tracer::new();
+        tracer.roster.roster = vec![profile(0, 2, 0), profile(1, 3, 0)];
+        let enemy = snapshot(100, 1, 3, Team::Blue, [240.0, 0.0], [0.0, 180.0]);
+        tracer.states.push(enemy);
+        for (tick, yaw) in [(100, 90.0), (101, 90.0), (102, 0.0), (103, 0.0)] {
+            let view = snapshot(tick, 0, 2, Team::Red, [0.0, 0.0], [0.0, yaw]);
+            tracer.states.push(view);
+        }
+        for (tick, yaw) in [(200, 0.0), (201, 180.0), (202, 180.0)] {
+            let view = snapshot(tick, 0, 2, Team::Red, [0.0, 0.0], [0.0, yaw]);
+            tracer.states.push(view);
+        }
+        tracer
+    }
+
+    #[test]
+    fn snaps_and_their_derivatives() {
+        let tracer = snap_tracer();
+        let features = aim_features(&tracer, &AimConfig::default());
+        let samples: Vec<&AimSample> = features
+            .iter()
+            .filter(|sample| sample.inner.roster_key == 0)
+            .map(|sample| &sample.inner)
+            .collect();
+        assert_eq!(samples.len(), 7);
+        assert!(samples.iter().all(|sample| sample.user_id == Some(2)));
+
+        let derivatives = |sample: &AimSample| {
+            [
+                sample.angular_velocity.is_some(),
+                sample.angular_acceleration.is_some(),
+                sample.angular_jerk.is_some(),
+            ]
+        };
+        // each derivative needs one more view of history, which a gap resets
+        assert_eq!(derivatives(samples[0]), [false, false, false]);
+        assert_eq!(derivatives(samples[1]), [true, false, false]);
+        assert_eq!(derivatives(samples[2]), [true, true, false]);
+        assert_eq!(derivatives(samples[3]), [true, true, true]);
+        assert_eq!(derivatives(samples[4]), [false, false, false]);
+        assert_eq!(derivatives(samples[5]), [true, false, false]);
+
+        // 90 degrees in a tick lands right on their head
+        let snap = samples[2];
+        assert!((snap.angular_velocity.unwrap() - 90.0 / 0.015).abs() < 1.0);
+        assert!(snap.snap);
+        assert_eq!(snap.snap_target, Some(3));
+        assert_eq!(snap.snap_target_key, Some(1));
+        assert_eq!(snap.snap_landed_tick, Some(102));
+        assert!(!samples[1].snap && !samples[3].snap);
+
+        // turning away is just as fast, but doesn't land on anyone
+        let away = samples[5];
+        assert!(away.snap);
+        assert_eq!((away.snap_target_key, away.snap_landed_tick), (None, None));
+    }
+
+    #[test]
+    fn crosshair_on_target_since() {
+        let tracer = snap_tracer();
+        let samples: Vec<(u32, CrosshairSample)> = crosshair(&tracer, HITBOX_RADIUS)
+            .into_iter()
+            .filter(|sample| sample.inner.roster_key == 0)
+            .map(|sample| (sample.tick, sample.inner))
+            .collect();
+        let since: Vec<(u32, Option<u32>)> = samples
+            .iter()
+            .map(|(tick, sample)| (*tick, sample.on_target_since))
+            .collect();
+        assert_eq!(
+            since,
+            [
+                (100, None),
+                (101, None),
+                (102, Some(102)),
+                (103, Some(102)),
+                (200, Some(102)),
+                (201, None),
+                (202, None),
+            ]
+        );
+        let (_, on_target) = &samples[2];
+        assert!(on_target.on_target);
+        assert_eq!((on_target.target, on_target.target_key), (Some(3), Some(1)));
+        assert!(on_target.head_offset.unwrap() < 1e-3);
+        assert!((on_target.distance.unwrap() - 240.0).abs() < 1.0);
+        // the enemy is still the closest one while facing away
+        let (_, away) = &samples[5];
+        assert!(!away.on_target);
+        assert_eq!(away.target_key, Some(1));
+    }
+}
@@ -34,12 +34,7 @@ fn pipe(
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    let jpath = args
-        .json_path
-        .as_ref()
-        .map(String::as_str)
-        .map(JsonPath::parse)
-        .transpose()?;
+    let jpath = args.json_path.as_deref().map(JsonPath::parse).transpose()?;
     let data = std::fs::read(args.file_name)?;
     let mut ostrm = {
         let stdout = std::io::stdout();
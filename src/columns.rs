@@ -0,0 +1,184 @@
+use polars::prelude::*;
+use polars_arrow::array::Utf8ViewArray;
+use serde::{Deserialize, Serialize};
+use tf_demo_parser::demo::parser::gamestateanalyser::{Class, PlayerState, Team};
+
+/// A value type that a column of `columnar!` can hold.
+pub trait Column: Sized {
+    fn into_series(name: &str, values: Vec<Self>) -> PolarsResult<Series>;
+}
+
+macro_rules! plain_columns {
+    ($($ty:ty),*) => {
+        $(impl Column for $ty {
+            fn into_series(name: &str, values: Vec<Self>) -> PolarsResult<Series> {
+                Ok(Series::new(name.into(), values))
+            }
+        })*
+    };
+}
+
+plain_columns!(
+    bool,
+    u8,
+    u16,
+    f32,
+    String,
+    Option<u16>,
+    Option<u32>,
+    Option<i32>,
+    Option<String>
+);
+
+/// A closed set of names, stored as a polars `Enum` so every demo gets the
+/// same categories and the same integer codes.
+pub trait Category: Copy + 'static {
+    const NAMES: &'static [&'static str];
+
+    fn index(self) -> usize;
+
+    fn name(self) -> &'static str {
+        Self::NAMES[self.index()]
+    }
+
+    fn rev_map() -> Arc<RevMapping> {
+        Arc::new(RevMapping::build_local(Utf8ViewArray::from_slice_values(
+            Self::NAMES,
+        )))
+    }
+
+    fn dtype() -> DataType {
+        DataType::Enum(Some(Self::rev_map()), Default::default())
+    }
+}
+
+macro_rules! categories {
+    ($(
+        $(#[$meta:meta])*
+        pub enum $name:ident { $($variant:ident => $label:literal,)* }
+    )*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+            pub enum $name {
+                $(#[serde(rename = $label)] $variant,)*
+            }
+
+            impl Category for $name {
+                const NAMES: &'static [&'static str] = &[$($label),*];
+
+                fn index(self) -> usize {
+                    self as usize
+                }
+            }
+
+            impl Column for $name {
+                fn into_series(name: &str, values: Vec<Self>) -> PolarsResult<Series> {
+                    let names: Vec<&str> = values.into_iter().map(Category::name).collect();
+                    Series::new(name, names).cast(&Self::dtype())
+                }
+            }
+        )*
+    };
+}
+
+categories! {
+    pub enum ClassName {
+        Scout => "scout",
+        Soldier => "soldier",
+        Pyro => "pyro",
+        Demoman => "demoman",
+        Heavy => "heavy",
+        Engineer => "engineer",
+        Medic => "medic",
+        Sniper => "sniper",
+        Spy => "spy",
+        Other => "other",
+    }
+
+    pub enum TeamName {
+        Blu => "blu",
+        Red => "red",
+        Spectator => "spectator",
+        Other => "other",
+    }
+
+    /// `Queue` is waiting to respawn
+    pub enum LifeState {
+        Alive => "alive",
+        Death => "death",
+        Dying => "dying",
+        Queue => "queue",
+    }
+
+    /// `Pause` for `SetPause`, `Timescale` for `host_timescale` other than 1
+    /// and `Gap` for ticks missing from the demo
+    pub enum PauseCause {
+        Pause => "pause",
+        Timescale => "timescale",
+        Gap => "gap",
+    }
+}
+
+impl From<Class> for ClassName {
+    fn from(class: Class) -> Self {
+        match class {
+            Class::Scout => Self::Scout,
+            Class::Soldier => Self::Soldier,
+            Class::Pyro => Self::Pyro,
+            Class::Demoman => Self::Demoman,
+            Class::Heavy => Self::Heavy,
+            Class::Engineer => Self::Engineer,
+            Class::Medic => Self::Medic,
+            Class::Sniper => Self::Sniper,
+            Class::Spy => Self::Spy,
+            Class::Other => Self::Other,
+        }
+    }
+}
+
+impl From<Team> for TeamName {
+    fn from(team: Team) -> Self {
+        match team {
+            Team::Blue => Self::Blu,
+            Team::Red => Self::Red,
+            Team::Spectator => Self::Spectator,
+            Team::Other => Self::Other,
+        }
+    }
+}
+
+impl From<PlayerState> for LifeState {
+    fn from(state: PlayerState) -> Self {
+        match state {
+            PlayerState::Alive => Self::Alive,
+            PlayerState::Death => Self::Death,
+            PlayerState::Dying => Self::Dying,
+            PlayerState::Respawnable => Self::Queue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_line_up_with_variants() {
+        assert_eq!(ClassName::Other.name(), "other");
+        assert_eq!(TeamName::Blu.name(), "blu");
+        assert_eq!(LifeState::Queue.name(), "queue");
+        assert_eq!(ClassName::NAMES.len(), ClassName::Other as usize + 1);
+    }
+
+    #[test]
+    fn series_carry_codes_and_names() {
+        let series = TeamName::into_series("team", vec![TeamName::Red, TeamName::Blu]).unwrap();
+        assert_eq!(series.dtype(), &TeamName::dtype());
+        let categories = series.categorical().unwrap();
+        let codes: Vec<Option<u32>> = categories.physical().into_iter().collect();
+        assert_eq!(codes, vec![Some(1), Some(0)]);
+        let names: Vec<Option<&str>> = categories.iter_str().collect();
+        assert_eq!(names, vec![Some("red"), Some("blu")]);
+    }
+}
@@ -1,12 +1,11 @@
-use thiserror::Error;
-use pyo3::{exceptions::PyValueError, PyErr};
-use tf_demo_parser::ParseError;
 use bitbuffer::BitError;
-use serde_json::Number;
-use serde_json_path::{ParseError as JsonPathParseError, AtMostOneError};
 use polars::error::PolarsError;
+use pyo3::{exceptions::PyValueError, PyErr};
 use serde_arrow::Error as ArrowError;
-
+use serde_json::Number;
+use serde_json_path::{AtMostOneError, ParseError as JsonPathParseError};
+use tf_demo_parser::ParseError;
+use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -41,6 +40,12 @@ pub enum Error {
 
     #[error("Polars error: {0}")]
     Polars(#[from] PolarsError),
+
+    #[error("Unknown table '{0}'")]
+    UnknownTable(String),
+
+    #[error("Table '{table}' is missing column '{column}'")]
+    MissingColumn { table: String, column: String },
 }
 
 impl From<Error> for PyErr {
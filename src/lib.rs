@@ -1,16 +1,36 @@
 
+pub mod aim;
+pub mod columns;
 pub mod errors;
+pub mod movement;
+pub mod pauses;
+pub mod player;
+pub mod pov;
+pub mod projectiles;
+pub mod reaction;
+pub mod schema;
 pub mod serialize;
+pub mod server;
+pub mod sounds;
+pub mod steamid;
+#[cfg(test)]
+mod testing;
 pub mod tracer;
+pub mod usercmd;
 
+use aim::AimConfig;
 use bitbuffer::BitRead;
+use pauses::Pause;
+use pov::Pov;
 use pyo3::prelude::*;
 use pyo3_polars::PyDataFrame;
 use pythonize::pythonize;
-use serde_arrow::schema::TracingOptions;
+use reaction::EngagementConfig;
+use tf_demo_parser::demo::packet::Packet;
 use tf_demo_parser::demo::{header::Header, parser::DemoParser};
 use tf_demo_parser::Demo;
-use tracer::{Roster, Tracer, WithTick};
+use tracer::{trace, Clock, ConsoleCommand, Roster, WithTick};
+use usercmd::UserCommand;
 
 use errors::*;
 use serialize::to_polars;
@@ -48,52 +68,216 @@ mod tests {
     }
 }
 
-fn is_pov_formatted(s: &str) -> bool {
-    if let Some((hostname, port)) = s.split_once(':') {
-        !hostname.is_empty() && port.parse::<u16>().is_ok()
-    } else {
-        false
-    }
-}
-
 #[pyclass(get_all)]
 pub struct DTrace {
     header: Option<PyObject>,
-    states: Option<PyDataFrame>,
-    events: Option<PyDataFrame>,
-    roster: Option<PyDataFrame>,
-    bounds: Option<PyDataFrame>,
+    states: PyDataFrame,
+    events: PyDataFrame,
+    roster: PyDataFrame,
+    /// every name a player went by, with the tick they took it
+    name_history: PyDataFrame,
+    /// when each player connected and disconnected
+    presence: PyDataFrame,
+    /// userinfo entries that couldn't be parsed and were skipped
+    warnings: PyDataFrame,
+    bounds: PyDataFrame,
+    projectiles: PyDataFrame,
+    usercmds: PyDataFrame,
+    /// commands the recording client ran, from binds, configs or typed in
+    console: PyDataFrame,
+    /// tick rate, map, game and version the server reported
+    server: Option<PyObject>,
+    /// every convar the server replicated, with the tick it was set
+    convars: PyDataFrame,
+    /// when the server was paused, ran at another `host_timescale` or the
+    /// demo skipped ticks
+    pauses: PyDataFrame,
+    /// every sound the server played, with its origin and volume
+    sounds: PyDataFrame,
+    /// the roster entry of the player that recorded a POV demo
+    pov_player: Option<PyObject>,
+    schema_version: u32,
+    /// the columns of every table as JSON, the same as `demoreel.SCHEMA`,
+    /// so a result keeps the schema it was made with
+    schema: String,
 }
 
 #[pyfunction]
-fn roster<'py>(py: Python<'py>, buffer: &[u8]) -> Result<Option<PyDataFrame>> {
+fn roster<'py>(py: Python<'py>, buffer: &[u8]) -> Result<PyDataFrame> {
     py.allow_threads(|| -> Result<_> {
-        let demo = Demo::new(&buffer);
+        let demo = Demo::new(buffer);
         let stream = demo.get_stream();
         let parser = DemoParser::new_with_analyser(stream, Roster::new());
         let (_header, roster) = parser.parse()?;
-        Ok(to_polars(roster.roster.as_slice(), None)?.map(PyDataFrame))
+        let tropt = schema::tracing_options();
+        let roster = to_polars(roster.roster.as_slice(), Some(tropt))?;
+        Ok(PyDataFrame(schema::conform("roster", roster)?))
     })
 }
 
-/// see if the server in the header is formatted like a hostname to determine if this is a pov demo
-/// This isn't foolproof and could be combined with checking header.nick against roster and for
-/// the presence of user commands if we want to account for intentional misrepresentation of this by players.
-/// returns a bool.
+/// Decide whether a demo was recorded from a player's point of view and by
+/// whom. Besides the header's server and nick, which are trivially spoofed,
+/// this looks for user commands and the data tables only sent to the
+/// recording client. The result is truthy for POV demos.
 #[pyfunction]
-fn is_pov<'py>(py: Python<'py>, buffer: &[u8]) -> Result<Option<bool>> {
+fn is_pov<'py>(py: Python<'py>, buffer: &[u8]) -> Result<Pov> {
+    py.allow_threads(|| -> Result<_> { Pov::scan(Demo::new(buffer)) })
+}
+
+/// Extract the user commands recorded in a POV demo, one row per command.
+#[pyfunction]
+fn usercmds<'py>(py: Python<'py>, buffer: &[u8]) -> Result<PyDataFrame> {
     py.allow_threads(|| -> Result<_> {
-        let demo = Demo::new(&buffer);
-        let mut stream = demo.get_stream();
-        let header = Header::read(&mut stream)?;
-        Ok(Some(is_pov_formatted(&header.server)))
+        let demo = Demo::new(buffer);
+        let mut usercmds = Vec::new();
+        let (_header, clock) = tracer::scan(demo, Clock::new(), |packet| {
+            if let Packet::UserCmd(packet) = packet {
+                usercmds.push(UserCommand::decode(packet)?);
+            }
+            Ok(())
+        })?;
+        let tropt = schema::tracing_options();
+        let usercmds = WithTick::to_polars(usercmds.into_iter(), Some(tropt))?;
+        Ok(PyDataFrame(schema::finish("usercmds", usercmds, &clock)?))
+    })
+}
+
+/// Extract the console commands recorded in a demo, one row per command.
+#[pyfunction]
+fn console<'py>(py: Python<'py>, buffer: &[u8]) -> Result<PyDataFrame> {
+    py.allow_threads(|| -> Result<_> {
+        let demo = Demo::new(buffer);
+        let mut console = Vec::new();
+        let (_header, clock) = tracer::scan(demo, Clock::new(), |packet| {
+            if let Packet::ConsoleCmd(packet) = packet {
+                console.push(ConsoleCommand::new(packet));
+            }
+            Ok(())
+        })?;
+        let tropt = schema::tracing_options();
+        let console = WithTick::to_polars(console.into_iter(), Some(tropt))?;
+        Ok(PyDataFrame(schema::finish("console", console, &clock)?))
+    })
+}
+
+/// Per player angular velocity, acceleration and jerk of the view, flagging
+/// snaps faster than `snap_threshold` degrees per second that land within
+/// `hitbox_radius` units of an enemy's head or chest within `window` ticks.
+#[pyfunction]
+#[pyo3(signature = (
+    buffer,
+    snap_threshold = aim::SNAP_THRESHOLD,
+    window = aim::SNAP_WINDOW,
+    hitbox_radius = aim::HITBOX_RADIUS,
+))]
+fn aim_features<'py>(
+    py: Python<'py>,
+    buffer: &[u8],
+    snap_threshold: f32,
+    window: u32,
+    hitbox_radius: f32,
+) -> Result<PyDataFrame> {
+    py.allow_threads(|| -> Result<_> {
+        let demo = Demo::new(buffer);
+        let (_header, dtrace) = trace(demo)?;
+        let config = AimConfig {
+            snap_threshold,
+            window,
+            hitbox_radius,
+        };
+        let features = aim::aim_features(&dtrace, &config);
+        let tropt = schema::tracing_options();
+        let features = WithTick::to_polars(features.into_iter(), Some(tropt))?;
+        Ok(PyDataFrame(schema::finish(
+            "aim_features",
+            features,
+            &dtrace.clock,
+        )?))
+    })
+}
+
+/// For every player at every tick, the enemy closest to their view ray and
+/// the angular offset in degrees to that enemy's head and chest. Every enemy
+/// is a candidate at every tick, so this takes time in proportion to ticks
+/// times players squared.
+#[pyfunction]
+#[pyo3(signature = (buffer, hitbox_radius = aim::HITBOX_RADIUS))]
+fn crosshair<'py>(py: Python<'py>, buffer: &[u8], hitbox_radius: f32) -> Result<PyDataFrame> {
+    py.allow_threads(|| -> Result<_> {
+        let demo = Demo::new(buffer);
+        let (_header, dtrace) = trace(demo)?;
+        let samples = aim::crosshair(&dtrace, hitbox_radius);
+        let tropt = schema::tracing_options();
+        let samples = WithTick::to_polars(samples.into_iter(), Some(tropt))?;
+        Ok(PyDataFrame(schema::finish(
+            "crosshair",
+            samples,
+            &dtrace.clock,
+        )?))
+    })
+}
+
+/// One row per engagement between an attacker and a victim, with the ticks
+/// the victim became visible to the attacker, the attacker started aiming at
+/// them and the first damage landed. Hits more than `gap` seconds apart start
+/// a new engagement, visibility is searched for up to `lookback` seconds
+/// before the first hit. The victim is visible when within a cone of `fov / 2`
+/// degrees around the attacker's crosshair and, only where `pvs_checked`
+/// because the attacker recorded the demo, in PVS.
+#[pyfunction]
+#[pyo3(signature = (
+    buffer,
+    fov = reaction::FOV,
+    gap = reaction::ENGAGEMENT_GAP,
+    lookback = reaction::LOOKBACK,
+))]
+fn engagements<'py>(
+    py: Python<'py>,
+    buffer: &[u8],
+    fov: f32,
+    gap: f32,
+    lookback: f32,
+) -> Result<PyDataFrame> {
+    py.allow_threads(|| -> Result<_> {
+        let demo = Demo::new(buffer);
+        let (_header, dtrace) = trace(demo)?;
+        let config = EngagementConfig { fov, gap, lookback };
+        let engagements = reaction::engagements(&dtrace, &config);
+        let tropt = schema::tracing_options();
+        let engagements = WithTick::to_polars(engagements.into_iter(), Some(tropt))?;
+        Ok(PyDataFrame(schema::finish(
+            "engagements",
+            engagements,
+            &dtrace.clock,
+        )?))
+    })
+}
+
+/// One row per player summarising their movement: how many ticks they spend
+/// on the ground between landing and jumping again, perfect bunny-hop counts
+/// and streaks, and how often their airborne turns are synchronised with
+/// their strafes.
+#[pyfunction]
+#[pyo3(signature = (buffer))]
+fn movement_features<'py>(py: Python<'py>, buffer: &[u8]) -> Result<PyDataFrame> {
+    py.allow_threads(|| -> Result<_> {
+        let demo = Demo::new(buffer);
+        let (_header, dtrace) = trace(demo)?;
+        let features = movement::movement_features(&dtrace);
+        let tropt = schema::tracing_options();
+        let features = to_polars(features.as_slice(), Some(tropt))?;
+        Ok(PyDataFrame(schema::finish(
+            "movement_features",
+            features,
+            &dtrace.clock,
+        )?))
     })
 }
 
 #[pyfunction]
 fn header<'py>(py: Python<'py>, buffer: &[u8]) -> Result<Option<PyObject>> {
     let header = py.allow_threads(|| -> Result<_> {
-        let demo = Demo::new(&buffer);
+        let demo = Demo::new(buffer);
         let mut stream = demo.get_stream();
         let header = Header::read(&mut stream)?;
         Ok(Some(header))
@@ -101,48 +285,93 @@ fn header<'py>(py: Python<'py>, buffer: &[u8]) -> Result<Option<PyObject>> {
     Ok(Some(pythonize(py, &header).unwrap().into()))
 }
 
-/// Trace all players, states, and instances of damage inflicted within a
-/// demo file, yielding the result as a set of polars dataframes.
+/// Trace all players, states, instances of damage inflicted and projectile
+/// paths within a demo file, yielding the result as a set of polars dataframes.
 #[pyfunction]
 #[pyo3(signature = (buffer))]
 fn dtrace<'py>(py: Python<'py>, buffer: &[u8]) -> Result<DTrace> {
-    let (header, states, events, roster, bounds) = py.allow_threads(|| -> Result<_> {
-        let demo = Demo::new(&buffer);
-        let stream = demo.get_stream();
-        let parser = DemoParser::new_with_analyser(stream, Tracer::new());
-        let (header, dtrace) = parser.parse()?;
-        let tropt = TracingOptions::default()
-            .allow_null_fields(true)
-            .string_dictionary_encoding(false);
-        let states = WithTick::to_polars(dtrace.states.into_iter(), Some(tropt.clone()))?;
+    let (header, server, pov_player, mut dtrace) = py.allow_threads(|| -> Result<_> {
+        let demo = Demo::new(buffer);
+        let (header, dtrace) = trace(demo)?;
+        let pov_player = dtrace.recorder(&header.nick).cloned();
+        let clock = dtrace.clock.clone();
+        let tropt = schema::tracing_options();
+        let states = dtrace.states.to_polars()?;
         let events = WithTick::to_polars(dtrace.events.into_iter(), Some(tropt.clone()))?;
         let bounds = WithTick::to_polars(dtrace.bounds.into_iter(), Some(tropt.clone()))?;
+        let projectiles =
+            WithTick::to_polars(dtrace.projectiles.updates.into_iter(), Some(tropt.clone()))?;
+        let usercmds = WithTick::to_polars(dtrace.usercmds.into_iter(), Some(tropt.clone()))?;
+        let console = WithTick::to_polars(dtrace.console.into_iter(), Some(tropt.clone()))?;
         let roster = to_polars(dtrace.roster.roster.as_slice(), Some(tropt.clone()))?;
-        Ok((
-            header,
-            states.map(PyDataFrame),
-            events.map(PyDataFrame),
-            roster.map(PyDataFrame),
-            bounds.map(PyDataFrame),
-        ))
+        let presence = to_polars(dtrace.roster.presence().as_slice(), Some(tropt.clone()))?;
+        let name_history =
+            WithTick::to_polars(dtrace.roster.name_history.into_iter(), Some(tropt.clone()))?;
+        let warnings =
+            WithTick::to_polars(dtrace.roster.warnings.into_iter(), Some(tropt.clone()))?;
+        let server = dtrace.server.details;
+        let convars = WithTick::to_polars(dtrace.server.convars.into_iter(), Some(tropt.clone()))?;
+        let pauses = Pause::to_polars(dtrace.pauses.pauses.as_slice(), Some(tropt.clone()))?;
+        let sounds = WithTick::to_polars(dtrace.sounds.sounds.into_iter(), Some(tropt.clone()))?;
+        let finish = |table, frame| schema::finish(table, frame, &clock);
+        let dtrace = DTrace {
+            header: None,
+            states: PyDataFrame(finish("states", states)?),
+            events: PyDataFrame(finish("events", events)?),
+            roster: PyDataFrame(finish("roster", roster)?),
+            name_history: PyDataFrame(finish("name_history", name_history)?),
+            presence: PyDataFrame(finish("presence", presence)?),
+            warnings: PyDataFrame(finish("warnings", warnings)?),
+            bounds: PyDataFrame(finish("bounds", bounds)?),
+            projectiles: PyDataFrame(finish("projectiles", projectiles)?),
+            usercmds: PyDataFrame(finish("usercmds", usercmds)?),
+            console: PyDataFrame(finish("console", console)?),
+            server: None,
+            convars: PyDataFrame(finish("convars", convars)?),
+            pauses: PyDataFrame(finish("pauses", pauses)?),
+            sounds: PyDataFrame(finish("sounds", sounds)?),
+            pov_player: None,
+            schema_version: schema::SCHEMA_VERSION,
+            schema: schema::EMBEDDED.to_string(),
+        };
+        Ok((header, server, pov_player, dtrace))
     })?;
-    let header = Some(pythonize(py, &header).unwrap().into());
-
-    let dtrace = DTrace {
-        header,
-        states,
-        events,
-        roster,
-        bounds,
-    };
+    dtrace.header = Some(pythonize(py, &header).unwrap().into());
+    dtrace.server = server
+        .map(|server| pythonize(py, &server).map_err(PyErr::from))
+        .transpose()?
+        .map(Into::into);
+    dtrace.pov_player = pov_player
+        .map(|profile| pythonize(py, &profile).map_err(PyErr::from))
+        .transpose()?
+        .map(Into::into);
     Ok(dtrace)
 }
 
+/// An empty dataframe with the columns and types of `table`, which is one of
+/// the tables on `DTrace` or the name of an analysis function such as
+/// `aim_features`. Columns only change along with `SCHEMA_VERSION`.
+#[pyfunction]
+#[pyo3(name = "schema")]
+fn table_schema(table: &str) -> Result<PyDataFrame> {
+    Ok(PyDataFrame(schema::empty(table)?))
+}
+
 #[pymodule]
 fn demoreel(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(dtrace, m)?)?;
     m.add_function(wrap_pyfunction!(roster, m)?)?;
     m.add_function(wrap_pyfunction!(header, m)?)?;
     m.add_function(wrap_pyfunction!(is_pov, m)?)?;
+    m.add_function(wrap_pyfunction!(usercmds, m)?)?;
+    m.add_function(wrap_pyfunction!(console, m)?)?;
+    m.add_function(wrap_pyfunction!(aim_features, m)?)?;
+    m.add_function(wrap_pyfunction!(crosshair, m)?)?;
+    m.add_function(wrap_pyfunction!(engagements, m)?)?;
+    m.add_function(wrap_pyfunction!(movement_features, m)?)?;
+    m.add_function(wrap_pyfunction!(table_schema, m)?)?;
+    m.add("SCHEMA_VERSION", schema::SCHEMA_VERSION)?;
+    m.add("TABLES", schema::TABLES.to_vec())?;
+    m.add("SCHEMA", schema::EMBEDDED)?;
     Ok(())
 }
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::aim::{has_usercmds, Timeline};
+use crate::tracer::Tracer;
+use crate::usercmd::UserCommand;
+
+/// a takeoff at most this many ticks after landing is a perfect hop
+pub const PERFECT_HOP_TICKS: u32 = 1;
+/// a takeoff at most this many ticks after landing counts as a hop at all
+pub const HOP_WINDOW: u32 = 10;
+/// yaw changes smaller than this many degrees per tick aren't strafes
+const MIN_TURN: f32 = 0.1;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MovementFeatures {
+    pub user_id: Option<u16>,
+    pub roster_key: u32,
+    pub jumps: u32,
+    pub hops: u32,
+    pub perfect_hops: u32,
+    pub perfect_hop_ratio: Option<f32>,
+    pub longest_hop_streak: u32,
+    /// ticks spent on the ground between landing and jumping again
+    pub mean_ground_ticks: Option<f32>,
+    pub ground_ticks_std: Option<f32>,
+    /// ticks between landing and pressing jump, from user commands
+    pub mean_press_offset: Option<f32>,
+    pub press_offset_std: Option<f32>,
+    /// share of airborne turns in the direction the player is strafing
+    pub strafe_sync: Option<f32>,
+    pub airborne_ticks: u32,
+    pub from_usercmd: bool,
+}
+
+#[derive(Clone, Copy)]
+struct MovementSample {
+    tick: u32,
+    on_ground: bool,
+    yaw: f32,
+    velocity_x: f32,
+    velocity_y: f32,
+    velocity_z: f32,
+}
+
+/// difference between two angles in degrees, wrapped to [-180, 180)
+fn wrap_degrees(delta: f32) -> f32 {
+    (delta + 180.0).rem_euclid(360.0) - 180.0
+}
+
+fn mean_std(values: &[f32]) -> (Option<f32>, Option<f32>) {
+    if values.is_empty() {
+        return (None, None);
+    }
+    let n = values.len() as f32;
+    let mean = values.iter().sum::<f32>() / n;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / n;
+    (Some(mean), Some(variance.sqrt()))
+}
+
+/// Per player jump timing relative to landing, perfect hop streaks and
+/// strafe synchronisation. For the player that recorded a POV demo, jump
+/// presses and strafe direction come from their user commands.
+pub fn movement_features(tracer: &Tracer) -> Vec<MovementFeatures> {
+    let timeline = Timeline::new(&tracer.states);
+    let mut keys: Vec<u32> = timeline.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .map(|key| {
+            let mut samples: Vec<MovementSample> = Vec::new();
+            for (tick, state) in timeline.states(key) {
+                let sample = MovementSample {
+                    tick,
+                    on_ground: *state.on_ground,
+                    yaw: *state.view_angle,
+                    velocity_x: *state.velocity_x,
+                    velocity_y: *state.velocity_y,
+                    velocity_z: *state.velocity_z,
+                };
+                match samples.last_mut() {
+                    Some(last) if last.tick == sample.tick => *last = sample,
+                    _ => samples.push(sample),
+                }
+            }
+            let usercmds: HashMap<u32, &UserCommand> = if has_usercmds(tracer, key) {
+                tracer
+                    .usercmds
+                    .iter()
+                    .map(|cmd| (cmd.tick, &cmd.inner))
+                    .collect()
+            } else {
+                HashMap::new()
+            };
+            let user_id = timeline.user_id(key);
+            features(user_id, key, &samples, tracer, &usercmds)
+        })
+        .collect()
+}
+
+fn features(
+    user_id: Option<u16>,
+    roster_key: u32,
+    samples: &[MovementSample],
+    tracer: &Tracer,
+    usercmds: &HashMap<u32, &UserCommand>,
+) -> MovementFeatures {
+    let mut jumps = 0;
+    let mut perfect_hops = 0;
+    let mut streak = 0;
+    let mut longest_hop_streak = 0;
+    let mut ground_ticks = Vec::new();
+    let mut landings = Vec::new();
+    let mut airborne_ticks = 0;
+    let (mut turns, mut synced) = (0u32, 0u32);
+
+    for pair in samples.windows(2) {
+        let [prev, next] = [pair[0], pair[1]];
+        if !prev.on_ground && next.on_ground {
+            landings.push(next.tick);
+        }
+        // walking off a ledge isn't a jump
+        if prev.on_ground && !next.on_ground && next.velocity_z > 0.0 {
+            jumps += 1;
+            let grounded = landings
+                .last()
+                .map(|landing| next.tick.saturating_sub(*landing));
+            match grounded {
+                Some(grounded) if grounded <= HOP_WINDOW => {
+                    ground_ticks.push(grounded as f32);
+                    if grounded <= PERFECT_HOP_TICKS {
+                        perfect_hops += 1;
+                        streak += 1;
+                        longest_hop_streak = longest_hop_streak.max(streak);
+                    } else {
+                        streak = 0;
+                    }
+                }
+                _ => streak = 0,
+            }
+        }
+
+        if !prev.on_ground && !next.on_ground {
+            airborne_ticks += next.tick.saturating_sub(prev.tick);
+            let turn = wrap_degrees(next.yaw - prev.yaw);
+            if turn.abs() < MIN_TURN {
+                continue;
+            }
+            // turning left increases yaw, so a synced strafe moves against the turn
+            let strafe = match usercmds.get(&next.tick) {
+                Some(cmd) => -cmd.side_move,
+                None => wrap_degrees(
+                    next.velocity_y.atan2(next.velocity_x).to_degrees()
+                        - prev.velocity_y.atan2(prev.velocity_x).to_degrees(),
+                ),
+            };
+            if strafe != 0.0 {
+                turns += 1;
+                if strafe.signum() == turn.signum() {
+                    synced += 1;
+                }
+            }
+        }
+    }
+
+    let mut press_offsets = Vec::new();
+    if !usercmds.is_empty() {
+        let presses: Vec<u32> = tracer
+            .usercmds
+            .windows(2)
+            .filter(|pair| !pair[0].inner.in_jump && pair[1].inner.in_jump)
+            .map(|pair| pair[1].tick)
+            .collect();
+        for landing in &landings {
+            let nearest = presses
+                .iter()
+                .map(|press| i64::from(*press) - i64::from(*landing))
+                .filter(|offset| offset.unsigned_abs() <= u64::from(HOP_WINDOW))
+                .min_by_key(|offset| offset.abs());
+            if let Some(offset) = nearest {
+                press_offsets.push(offset as f32);
+            }
+        }
+    }
+
+    let hops = ground_ticks.len() as u32;
+    let (mean_ground_ticks, ground_ticks_std) = mean_std(&ground_ticks);
+    let (mean_press_offset, press_offset_std) = mean_std(&press_offsets);
+    MovementFeatures {
+        user_id,
+        roster_key,
+        jumps,
+        hops,
+        perfect_hops,
+        perfect_hop_ratio: (hops > 0).then(|| perfect_hops as f32 / hops as f32),
+        longest_hop_streak,
+        mean_ground_ticks,
+        ground_ticks_std,
+        mean_press_offset,
+        press_offset_std,
+        strafe_sync: (turns > 0).then(|| synced as f32 / turns as f32),
+        airborne_ticks,
+        from_usercmd: !usercmds.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_yaw_across_the_seam() {
+        assert_eq!(wrap_degrees(-179.0 - 179.0), 2.0);
+        assert_eq!(wrap_degrees(10.0 - 350.0), 20.0);
+        assert_eq!(wrap_degrees(90.0), 90.0);
+    }
+
+    fn sample(tick: u32, on_ground: bool, yaw: f32, [x, y, z]: [f32; 3]) -> MovementSample {
+        MovementSample {
+            tick,
+            on_ground,
+            yaw,
+            velocity_x: x,
+            velocity_y: y,
+            velocity_z: z,
+        }
+    }
+
+    #[test]
+    fn hops_streaks_and_strafe_sync() {
+        let samples = [
+            // a standing jump isn't a hop
+            sample(0, true, 0.0, [100.0, 0.0, 0.0]),
+            sample(1, false, 0.0, [100.0, 0.0, 200.0]),
+            // turning left while the velocity turns left is synced, then
+            // turning left while it turns right isn't
+            sample(2, false, 10.0, [100.0, 10.0, 100.0]),
+            sample(3, false, 20.0, [100.0, 0.0, 0.0]),
+            sample(4, false, 20.0, [100.0, 0.0, -100.0]),
+            // two perfect hops
+            sample(5, true, 20.0, [100.0, 0.0, 0.0]),
+            sample(6, false, 20.0, [100.0, 0.0, 200.0]),
+            // turning without strafing isn't counted either way
+            sample(7, false, 30.0, [100.0, 0.0, -100.0]),
+            sample(8, true, 30.0, [100.0, 0.0, 0.0]),
+            sample(9, false, 30.0, [100.0, 0.0, 200.0]),
+            // a late hop ends the streak
+            sample(10, true, 30.0, [100.0, 0.0, 0.0]),
+            sample(13, true, 30.0, [100.0, 0.0, 0.0]),
+            sample(14, false, 30.0, [100.0, 0.0, 200.0]),
+            // walking off a ledge isn't a jump
+            sample(15, true, 30.0, [100.0, 0.0, 0.0]),
+            sample(16, true, 30.0, [100.0, 0.0, 0.0]),
+            sample(17, false, 30.0, [100.0, 0.0, -50.0]),
+            sample(18, true, 30.0, [100.0, 0.0, 0.0]),
+        ];
+        let features = features(Some(2), 0, &samples, &Tracer::new(), &HashMap::new());
+        assert_eq!((features.user_id, features.roster_key), (Some(2), 0));
+        assert_eq!(features.jumps, 4);
+        assert_eq!((features.hops, features.perfect_hops), (3, 2));
+        assert_eq!(features.perfect_hop_ratio, Some(2.0 / 3.0));
+        assert_eq!(features.longest_hop_streak, 2);
+        assert_eq!(features.mean_ground_ticks, Some(2.0));
+        assert_eq!(features.airborne_ticks, 4);
+        assert_eq!(features.strafe_sync, Some(0.5));
+        assert_eq!(features.mean_press_offset, None);
+        assert!(!features.from_usercmd);
+    }
+}
@@ -0,0 +1,162 @@
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_arrow::schema::TracingOptions;
+use tf_demo_parser::demo::message::Message;
+
+use crate::columns::{Category, PauseCause};
+use crate::errors::Result;
+use crate::serialize::to_polars;
+use crate::tracer::Clock;
+
+/// STV only records every few ticks, so anything shorter than this is just
+/// the snapshot rate and not the server stalling.
+const MIN_GAP_S: f32 = 1.0;
+
+/// A stretch of the demo where game time didn't run at its normal pace.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Pause {
+    pub start_tick: u32,
+    /// the demo's last tick if it was still going when the demo ended
+    pub end_tick: u32,
+    pub cause: PauseCause,
+    /// the `host_timescale` in effect, only set for `timescale`
+    pub timescale: Option<f32>,
+}
+
+impl Pause {
+    fn new(cause: PauseCause, start_tick: u32, end_tick: u32, timescale: Option<f32>) -> Self {
+        Self {
+            start_tick,
+            end_tick,
+            cause,
+            timescale,
+        }
+    }
+
+    /// like `to_polars`, but with `cause` as an `Enum` column
+    pub fn to_polars(pauses: &[Self], config: Option<TracingOptions>) -> Result<DataFrame> {
+        let mut frame = to_polars(pauses, config)?;
+        let cause = frame.column("cause")?.cast(&PauseCause::dtype())?;
+        frame.with_column(cause)?;
+        Ok(frame)
+    }
+}
+
+/// Server pauses, timescale changes and gaps between ticks, in the order
+/// they ended.
+#[derive(Default)]
+pub struct Pauses {
+    pub pauses: Vec<Pause>,
+    /// when the open `SetPause` started
+    paused: Option<u32>,
+    /// when the current `host_timescale` was set, and what to
+    timescale: Option<(u32, f32)>,
+    last_tick: Option<u32>,
+}
+
+impl Pauses {
+    pub fn handle_message(&mut self, message: &Message, tick: u32) {
+        match message {
+            Message::SetPause(message) => self.set_paused(message.pause, tick),
+            Message::SetConVar(message) => {
+                for var in &message.vars {
+                    if var.key == "host_timescale" {
+                        if let Ok(timescale) = var.value.trim().parse() {
+                            self.set_timescale(timescale, tick);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// record a gap if `tick` is more than `MIN_GAP_S` after the last one
+    pub fn handle_tick(&mut self, tick: u32, clock: &Clock) {
+        if let Some(last) = self.last_tick {
+            if clock.elapsed(last, tick) > MIN_GAP_S {
+                self.pauses
+                    .push(Pause::new(PauseCause::Gap, last, tick, None));
+            }
+        }
+        self.last_tick = Some(self.last_tick.map_or(tick, |last| last.max(tick)));
+    }
+
+    fn set_paused(&mut self, paused: bool, tick: u32) {
+        match (self.paused, paused) {
+            (None, true) => self.paused = Some(tick),
+            (Some(start), false) => {
+                self.pauses
+                    .push(Pause::new(PauseCause::Pause, start, tick, None));
+                self.paused = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn set_timescale(&mut self, timescale: f32, tick: u32) {
+        if let Some((start, previous)) = self.timescale.take() {
+            if previous == timescale {
+                self.timescale = Some((start, previous));
+                return;
+            }
+            self.pauses.push(Pause::new(
+                PauseCause::Timescale,
+                start,
+                tick,
+                Some(previous),
+            ));
+        }
+        if timescale != 1.0 {
+            self.timescale = Some((tick, timescale));
+        }
+    }
+
+    /// close whatever is still open at the last tick of the demo
+    pub fn finish(&mut self) {
+        let end = self.last_tick.unwrap_or_default();
+        if let Some(start) = self.paused.take() {
+            self.pauses
+                .push(Pause::new(PauseCause::Pause, start, end, None));
+        }
+        if let Some((start, timescale)) = self.timescale.take() {
+            self.pauses.push(Pause::new(
+                PauseCause::Timescale,
+                start,
+                end,
+                Some(timescale),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals() {
+        let clock = Clock::new();
+        let mut pauses = Pauses::default();
+        pauses.handle_tick(0, &clock);
+        pauses.handle_tick(3, &clock);
+        pauses.set_paused(true, 10);
+        pauses.set_paused(true, 12);
+        pauses.set_paused(false, 20);
+        pauses.set_timescale(0.5, 30);
+        pauses.set_timescale(0.5, 35);
+        pauses.set_timescale(1.0, 40);
+        pauses.set_timescale(2.0, 50);
+        pauses.handle_tick(500, &clock);
+        pauses.finish();
+        assert_eq!(
+            pauses.pauses,
+            vec![
+                Pause::new(PauseCause::Pause, 10, 20, None),
+                Pause::new(PauseCause::Timescale, 30, 40, Some(0.5)),
+                Pause::new(PauseCause::Gap, 3, 500, None),
+                Pause::new(PauseCause::Timescale, 50, 500, Some(2.0)),
+            ]
+        );
+    }
+}
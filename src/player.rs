@@ -0,0 +1,678 @@
+use std::collections::HashMap;
+
+use tf_demo_parser::demo::message::packetentities::{
+    PacketEntitiesMessage, PacketEntity, UpdateType,
+};
+use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::parser::gamestateanalyser::Class;
+use tf_demo_parser::demo::sendprop::SendPropIdentifier;
+use tf_demo_parser::demo::vector::{Vector, VectorXY};
+use tf_demo_parser::ParserState;
+
+use crate::tracer::{handle_to_index, Clock};
+
+const PLAYER_COND: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCond");
+const CONDITION_BITS: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFPlayerConditionListExclusive", "_condition_bits");
+const PLAYER_COND_EX: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCondEx");
+const PLAYER_COND_EX2: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCondEx2");
+const PLAYER_COND_EX3: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCondEx3");
+const PLAYER_COND_EX4: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFPlayerShared", "m_nPlayerCondEx4");
+const ACTIVE_WEAPON: SendPropIdentifier =
+    SendPropIdentifier::new("DT_BaseCombatCharacter", "m_hActiveWeapon");
+const MY_WEAPONS: [SendPropIdentifier; MAX_WEAPONS] = [
+    SendPropIdentifier::new("m_hMyWeapons", "000"),
+    SendPropIdentifier::new("m_hMyWeapons", "001"),
+    SendPropIdentifier::new("m_hMyWeapons", "002"),
+    SendPropIdentifier::new("m_hMyWeapons", "003"),
+    SendPropIdentifier::new("m_hMyWeapons", "004"),
+    SendPropIdentifier::new("m_hMyWeapons", "005"),
+    SendPropIdentifier::new("m_hMyWeapons", "006"),
+    SendPropIdentifier::new("m_hMyWeapons", "007"),
+];
+const ITEM_DEFINITION_INDEX: SendPropIdentifier =
+    SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iItemDefinitionIndex");
+const CLIP: SendPropIdentifier = SendPropIdentifier::new("DT_LocalWeaponData", "m_iClip1");
+const AMMO: [SendPropIdentifier; AMMO_TYPES] = [
+    SendPropIdentifier::new("m_iAmmo", "000"),
+    SendPropIdentifier::new("m_iAmmo", "001"),
+    SendPropIdentifier::new("m_iAmmo", "002"),
+    SendPropIdentifier::new("m_iAmmo", "003"),
+];
+const LOCAL_ORIGIN: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_vecOrigin");
+const NON_LOCAL_ORIGIN: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_vecOrigin");
+const LOCAL_ORIGIN_Z: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFLocalPlayerExclusive", "m_vecOrigin[2]");
+const NON_LOCAL_ORIGIN_Z: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFNonLocalPlayerExclusive", "m_vecOrigin[2]");
+const VELOCITY_X: SendPropIdentifier =
+    SendPropIdentifier::new("DT_LocalPlayerExclusive", "m_vecVelocity[0]");
+const VELOCITY_Y: SendPropIdentifier =
+    SendPropIdentifier::new("DT_LocalPlayerExclusive", "m_vecVelocity[1]");
+const VELOCITY_Z: SendPropIdentifier =
+    SendPropIdentifier::new("DT_LocalPlayerExclusive", "m_vecVelocity[2]");
+const VIEW_OFFSET_Z: SendPropIdentifier =
+    SendPropIdentifier::new("DT_LocalPlayerExclusive", "m_vecViewOffset[2]");
+const DUCKED: SendPropIdentifier = SendPropIdentifier::new("DT_Local", "m_bDucked");
+const FLAGS: SendPropIdentifier = SendPropIdentifier::new("DT_BasePlayer", "m_fFlags");
+
+const FL_ONGROUND: u32 = 1 << 0;
+const FL_DUCKING: u32 = 1 << 1;
+
+/// eye height of a crouched player of any class
+const DUCK_VIEW_HEIGHT: f32 = 45.0;
+
+/// primary, secondary and melee
+pub const LOADOUT_SLOTS: usize = 3;
+pub const SLOT_PRIMARY: usize = 0;
+pub const SLOT_SECONDARY: usize = 1;
+pub const SLOT_MELEE: usize = 2;
+
+/// `m_hMyWeapons` has room for 48, but even an engineer with a grappling hook
+/// only fills the first few
+const MAX_WEAPONS: usize = 8;
+
+/// `m_iAmmo` is indexed by TF2 ammo type, only the local player receives it
+pub const AMMO_TYPES: usize = 4;
+pub const AMMO_PRIMARY: usize = 1;
+pub const AMMO_SECONDARY: usize = 2;
+pub const AMMO_METAL: usize = 3;
+
+/// The loadout slot a weapon's server class goes in. Weapons are handed out
+/// in whatever order the server gives them, so their position in
+/// `m_hMyWeapons` says nothing about the slot. Multi-class items are
+/// translated into a class specific entity, so the server class alone
+/// decides. PDAs, sappers and the like have no slot.
+pub fn weapon_slot(class_name: &str) -> Option<usize> {
+    match class_name {
+        "CTFScatterGun"
+        | "CTFSodaPopper"
+        | "CTFPEPBrawlerBlaster"
+        | "CTFPistol_ScoutPrimary"
+        | "CTFRocketLauncher"
+        | "CTFRocketLauncher_DirectHit"
+        | "CTFRocketLauncher_AirStrike"
+        | "CTFParticleCannon"
+        | "CTFFlameThrower"
+        | "CTFWeaponFlameBall"
+        | "CTFGrenadeLauncher"
+        | "CTFCannon"
+        | "CTFParachute_Primary"
+        | "CTFMinigun"
+        | "CTFShotgun_Primary"
+        | "CTFShotgun_Revenge"
+        | "CTFShotgunBuildingRescue"
+        | "CTFDRGPomson"
+        | "CTFSyringeGun"
+        | "CTFCrossbow"
+        | "CTFSniperRifle"
+        | "CTFSniperRifleDecap"
+        | "CTFSniperRifleClassic"
+        | "CTFCompoundBow"
+        | "CTFRevolver" => Some(SLOT_PRIMARY),
+        "CTFPistol"
+        | "CTFPistol_Scout"
+        | "CTFPistol_ScoutSecondary"
+        | "CTFLunchBox"
+        | "CTFLunchBox_Drink"
+        | "CTFJar"
+        | "CTFJarMilk"
+        | "CTFJarGas"
+        | "CTFCleaver"
+        | "CTFShotgun_Soldier"
+        | "CTFShotgun_HWG"
+        | "CTFShotgun_Pyro"
+        | "CTFBuffItem"
+        | "CTFRaygun"
+        | "CTFParachute_Secondary"
+        | "CTFFlareGun"
+        | "CTFFlareGun_Revenge"
+        | "CTFRocketPack"
+        | "CTFPipebombLauncher"
+        | "CTFLaserPointer"
+        | "CTFMechanicalArm"
+        | "CTFMedigun"
+        | "CTFSMG"
+        | "CTFChargedSMG" => Some(SLOT_SECONDARY),
+        "CTFBat" | "CTFBat_Wood" | "CTFBat_Fish" | "CTFBat_Giftwrap" | "CTFShovel"
+        | "CTFFireAxe" | "CTFBreakableSign" | "CTFSlap" | "CTFBottle" | "CTFSword"
+        | "CTFKatana" | "CTFStickBomb" | "CTFFists" | "CTFWrench" | "CTFRobotArm"
+        | "CTFBonesaw" | "CTFClub" | "CTFKnife" => Some(SLOT_MELEE),
+        _ => None,
+    }
+}
+
+/// `ETFCond` values, see `tf_shareddefs.h`
+pub mod cond {
+    pub const ZOOMED: u32 = 1;
+    pub const DISGUISED: u32 = 3;
+    pub const STEALTHED: u32 = 4;
+    pub const INVULNERABLE: u32 = 5;
+    pub const TAUNTING: u32 = 7;
+    pub const INVULNERABLE_WEARINGOFF: u32 = 8;
+    pub const STEALTHED_BLINK: u32 = 9;
+    pub const CRITBOOSTED: u32 = 11;
+    pub const PHASE: u32 = 14;
+    pub const STUNNED: u32 = 15;
+    pub const OFFENSEBUFF: u32 = 16;
+    pub const SHIELD_CHARGE: u32 = 17;
+    pub const ENERGY_BUFF: u32 = 19;
+    pub const BURNING: u32 = 22;
+    pub const HEALTH_OVERHEALED: u32 = 23;
+    pub const URINE: u32 = 24;
+    pub const BLEEDING: u32 = 25;
+    pub const MAD_MILK: u32 = 27;
+    pub const MARKEDFORDEATH: u32 = 30;
+    pub const CRITBOOSTED_PUMPKIN: u32 = 33;
+    pub const CRITBOOSTED_USER_BUFF: u32 = 34;
+    pub const CRITBOOSTED_DEMO_CHARGE: u32 = 35;
+    pub const CRITBOOSTED_FIRST_BLOOD: u32 = 37;
+    pub const CRITBOOSTED_BONUS_TIME: u32 = 38;
+    pub const CRITBOOSTED_CTF_CAPTURE: u32 = 39;
+    pub const CRITBOOSTED_ON_KILL: u32 = 40;
+    pub const CRITBOOSTED_RAGE_BUFF: u32 = 44;
+    pub const MARKEDFORDEATH_SILENT: u32 = 48;
+    pub const INVULNERABLE_HIDE_UNLESS_DAMAGED: u32 = 51;
+    pub const INVULNERABLE_USER_BUFF: u32 = 52;
+    pub const CRITBOOSTED_CARD_EFFECT: u32 = 56;
+    pub const INVULNERABLE_CARD_EFFECT: u32 = 57;
+    pub const STEALTHED_USER_BUFF: u32 = 64;
+    pub const STEALTHED_USER_BUFF_FADING: u32 = 66;
+    pub const MINICRITBOOSTED_ON_KILL: u32 = 78;
+}
+
+/// The condition bitfield of a player, split over `m_nPlayerCond` and the
+/// `m_nPlayerCondEx*` props in words of 32 conditions each.
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub struct Conditions([u32; 5]);
+
+impl Conditions {
+    pub fn has(&self, cond: u32) -> bool {
+        self.0
+            .get((cond / 32) as usize)
+            .is_some_and(|word| word & (1 << (cond % 32)) != 0)
+    }
+
+    pub fn any(&self, conds: &[u32]) -> bool {
+        conds.iter().any(|cond| self.has(*cond))
+    }
+
+    pub fn ubered(&self) -> bool {
+        self.any(&[
+            cond::INVULNERABLE,
+            cond::INVULNERABLE_WEARINGOFF,
+            cond::INVULNERABLE_HIDE_UNLESS_DAMAGED,
+            cond::INVULNERABLE_USER_BUFF,
+            cond::INVULNERABLE_CARD_EFFECT,
+        ])
+    }
+
+    pub fn kritzed(&self) -> bool {
+        self.any(&[
+            cond::CRITBOOSTED,
+            cond::CRITBOOSTED_PUMPKIN,
+            cond::CRITBOOSTED_USER_BUFF,
+            cond::CRITBOOSTED_DEMO_CHARGE,
+            cond::CRITBOOSTED_FIRST_BLOOD,
+            cond::CRITBOOSTED_BONUS_TIME,
+            cond::CRITBOOSTED_CTF_CAPTURE,
+            cond::CRITBOOSTED_ON_KILL,
+            cond::CRITBOOSTED_RAGE_BUFF,
+            cond::CRITBOOSTED_CARD_EFFECT,
+        ])
+    }
+
+    pub fn mini_crit(&self) -> bool {
+        self.any(&[
+            cond::OFFENSEBUFF,
+            cond::ENERGY_BUFF,
+            cond::MINICRITBOOSTED_ON_KILL,
+        ])
+    }
+
+    pub fn cloaked(&self) -> bool {
+        self.any(&[
+            cond::STEALTHED,
+            cond::STEALTHED_BLINK,
+            cond::STEALTHED_USER_BUFF,
+            cond::STEALTHED_USER_BUFF_FADING,
+        ])
+    }
+
+    pub fn marked_for_death(&self) -> bool {
+        self.any(&[cond::MARKEDFORDEATH, cond::MARKEDFORDEATH_SILENT])
+    }
+}
+
+/// Per player props that `GameStateAnalyser` doesn't integrate.
+#[derive(Default, Clone)]
+pub struct PlayerExtras {
+    pub conditions: Conditions,
+    /// entity index of the weapon the player is holding
+    pub active_weapon: Option<u32>,
+    /// entity indices of every weapon the player carries, see `PlayerProps::loadout`
+    pub weapons: [Option<u32>; MAX_WEAPONS],
+    /// reserve ammo by ammo type
+    pub ammo: [Option<i32>; AMMO_TYPES],
+    /// networked for the local player, differenced from origin updates otherwise
+    pub velocity: Vector,
+    /// `m_fFlags`
+    pub flags: u32,
+    /// only networked for the local player
+    pub ducked: Option<bool>,
+    /// only networked for the local player
+    pub view_offset: Option<f32>,
+    networked_velocity: bool,
+    origin: Option<(u32, Vector)>,
+}
+
+impl PlayerExtras {
+    pub fn on_ground(&self) -> bool {
+        self.flags & FL_ONGROUND != 0
+    }
+
+    /// whether we received any of the data tables only sent to the recording client
+    pub fn is_local(&self) -> bool {
+        self.networked_velocity
+            || self.view_offset.is_some()
+            || self.ducked.is_some()
+            || self.ammo.iter().any(Option::is_some)
+    }
+
+    /// whether `weapon` is in the player's hands or loadout
+    pub fn carries(&self, weapon: u32) -> bool {
+        self.active_weapon == Some(weapon) || self.weapons.contains(&Some(weapon))
+    }
+
+    pub fn ducking(&self) -> bool {
+        self.ducked.unwrap_or(self.flags & FL_DUCKING != 0)
+    }
+
+    /// height of the eyes above the origin, the networked view offset when we
+    /// have it, otherwise the class default
+    pub fn eye_height(&self, class: Class) -> f32 {
+        if let Some(view_offset) = self.view_offset {
+            return view_offset;
+        }
+        if self.ducking() {
+            return DUCK_VIEW_HEIGHT;
+        }
+        match class {
+            Class::Scout => 65.0,
+            Class::Soldier | Class::Pyro | Class::Demoman | Class::Engineer => 68.0,
+            Class::Heavy | Class::Medic | Class::Sniper | Class::Spy => 75.0,
+            Class::Other => 68.0,
+        }
+    }
+
+    fn handle_entity(
+        &mut self,
+        entity: &PacketEntity,
+        tick: u32,
+        clock: &Clock,
+        parser_state: &ParserState,
+    ) {
+        let mut origin = self.origin.map(|(_, origin)| origin).unwrap_or_default();
+        let mut moved = false;
+        for prop in entity.props(parser_state) {
+            let value = i64::try_from(&prop.value).unwrap_or_default();
+            match prop.identifier {
+                LOCAL_ORIGIN | NON_LOCAL_ORIGIN => {
+                    if let Ok(VectorXY { x, y }) = VectorXY::try_from(&prop.value) {
+                        origin.x = x;
+                        origin.y = y;
+                        moved = true;
+                    }
+                }
+                LOCAL_ORIGIN_Z | NON_LOCAL_ORIGIN_Z => {
+                    if let Ok(z) = f32::try_from(&prop.value) {
+                        origin.z = z;
+                        moved = true;
+                    }
+                }
+                VELOCITY_X => {
+                    self.velocity.x = f32::try_from(&prop.value).unwrap_or_default();
+                    self.networked_velocity = true;
+                }
+                VELOCITY_Y => {
+                    self.velocity.y = f32::try_from(&prop.value).unwrap_or_default();
+                    self.networked_velocity = true;
+                }
+                VELOCITY_Z => {
+                    self.velocity.z = f32::try_from(&prop.value).unwrap_or_default();
+                    self.networked_velocity = true;
+                }
+                VIEW_OFFSET_Z => self.view_offset = f32::try_from(&prop.value).ok(),
+                DUCKED => self.ducked = Some(value != 0),
+                FLAGS => self.flags = value as u32,
+                PLAYER_COND | CONDITION_BITS => self.conditions.0[0] = value as u32,
+                PLAYER_COND_EX => self.conditions.0[1] = value as u32,
+                PLAYER_COND_EX2 => self.conditions.0[2] = value as u32,
+                PLAYER_COND_EX3 => self.conditions.0[3] = value as u32,
+                PLAYER_COND_EX4 => self.conditions.0[4] = value as u32,
+                ACTIVE_WEAPON => self.active_weapon = handle_to_index(value),
+                identifier => {
+                    if let Some(index) = MY_WEAPONS.iter().position(|id| *id == identifier) {
+                        self.weapons[index] = handle_to_index(value);
+                    } else if let Some(kind) = AMMO.iter().position(|id| *id == identifier) {
+                        self.ammo[kind] = Some(value as i32);
+                    }
+                }
+            }
+        }
+        // an update without an origin means the player is where they were,
+        // so a player who stopped doesn't keep the velocity they had
+        if moved || self.origin.is_some() {
+            if let Some((last_tick, last)) = self.origin.filter(|_| !self.networked_velocity) {
+                let elapsed = clock.elapsed(last_tick, tick);
+                if elapsed > 0.0 {
+                    self.velocity = Vector {
+                        x: (origin.x - last.x) / elapsed,
+                        y: (origin.y - last.y) / elapsed,
+                        z: (origin.z - last.z) / elapsed,
+                    };
+                }
+            }
+            self.origin = Some((tick, origin));
+        }
+    }
+}
+
+/// A weapon (or other econ item) entity.
+#[derive(Clone)]
+pub struct Weapon {
+    pub class: String,
+    pub def_index: Option<u32>,
+    /// rounds in the clip, only networked to the weapon's owner
+    pub clip: Option<i32>,
+}
+
+/// Tracks `PlayerExtras` for every `CTFPlayer` entity and the weapons they
+/// refer to, keyed by entity index.
+#[derive(Default)]
+pub struct PlayerProps {
+    players: HashMap<u32, PlayerExtras>,
+    weapons: HashMap<u32, Weapon>,
+}
+
+impl PlayerProps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, entity: u32) -> Option<&PlayerExtras> {
+        self.players.get(&entity)
+    }
+
+    /// entity index of the player that recorded a POV demo
+    pub fn local_player(&self) -> Option<u32> {
+        self.players
+            .iter()
+            .find(|(_, extras)| extras.is_local())
+            .map(|(entity, _)| *entity)
+    }
+
+    pub fn weapon(&self, entity: Option<u32>) -> Option<&Weapon> {
+        entity.and_then(|entity| self.weapons.get(&entity))
+    }
+
+    /// entity indices of the primary, secondary and melee weapons of a player
+    pub fn loadout(&self, extras: &PlayerExtras) -> [Option<u32>; LOADOUT_SLOTS] {
+        let mut loadout = [None; LOADOUT_SLOTS];
+        for entity in extras.weapons.iter().flatten() {
+            let slot = self
+                .weapons
+                .get(entity)
+                .and_then(|weapon| weapon_slot(&weapon.class));
+            if let Some(slot) = slot {
+                loadout[slot] = Some(*entity);
+            }
+        }
+        loadout
+    }
+
+    pub fn handle_message(
+        &mut self,
+        message: &Message,
+        tick: u32,
+        clock: &Clock,
+        parser_state: &ParserState,
+    ) {
+        if let Message::PacketEntities(message) = message {
+            self.handle_entities(message, tick, clock, parser_state);
+        }
+    }
+
+    fn handle_entities(
+        &mut self,
+        message: &PacketEntitiesMessage,
+        tick: u32,
+        clock: &Clock,
+        parser_state: &ParserState,
+    ) {
+        for entity in &message.entities {
+            let Some(class) = parser_state
+                .server_classes
+                .get(usize::from(entity.server_class))
+            else {
+                continue;
+            };
+            let id = u32::from(entity.entity_index);
+            if class.name.as_str() == "CTFPlayer" {
+                self.handle_player(id, entity, tick, clock, parser_state);
+            } else if matches!(entity.update_type, UpdateType::Delete) {
+                self.weapons.remove(&id);
+            } else {
+                self.handle_item(id, class.name.as_str(), entity, parser_state);
+            }
+        }
+        for id in &message.removed_entities {
+            self.players.remove(&u32::from(*id));
+            self.weapons.remove(&u32::from(*id));
+        }
+    }
+
+    fn handle_player(
+        &mut self,
+        id: u32,
+        entity: &PacketEntity,
+        tick: u32,
+        clock: &Clock,
+        parser_state: &ParserState,
+    ) {
+        // whoever gets the slot next mustn't be differenced against the
+        // last position of the previous player
+        match entity.update_type {
+            UpdateType::Delete => {
+                self.players.remove(&id);
+                return;
+            }
+            UpdateType::Enter => {
+                self.players.remove(&id);
+            }
+            _ => {}
+        }
+        self.players
+            .entry(id)
+            .or_default()
+            .handle_entity(entity, tick, clock, parser_state);
+    }
+
+    fn handle_item(
+        &mut self,
+        id: u32,
+        class: &str,
+        entity: &PacketEntity,
+        parser_state: &ParserState,
+    ) {
+        if matches!(entity.update_type, UpdateType::Enter) {
+            self.weapons.remove(&id);
+        }
+        let mut def_index = None;
+        let mut clip = None;
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ITEM_DEFINITION_INDEX => def_index = i64::try_from(&prop.value).ok(),
+                CLIP => clip = i64::try_from(&prop.value).ok(),
+                _ => {}
+            }
+        }
+        if def_index.is_some() {
+            self.weapons.entry(id).or_insert_with(|| Weapon {
+                class: class.to_string(),
+                def_index: None,
+                clip: None,
+            });
+        }
+        if let Some(weapon) = self.weapons.get_mut(&id) {
+            if let Some(def_index) = def_index {
+                weapon.def_index = Some(def_index as u32);
+            }
+            if let Some(clip) = clip {
+                weapon.clip = Some(clip as i32);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{entity, parser_state};
+    use tf_demo_parser::demo::sendprop::SendPropValue;
+
+    fn moved_to(x: f32, y: f32, z: f32) -> PacketEntity {
+        let props = vec![
+            (NON_LOCAL_ORIGIN, SendPropValue::VectorXY(VectorXY { x, y })),
+            (NON_LOCAL_ORIGIN_Z, SendPropValue::Float(z)),
+        ];
+        entity(1, 0, UpdateType::Preserve, props)
+    }
+
+    #[test]
+    fn velocity_from_origin_updates() {
+        let state = ParserState::new(24, |_| true, false);
+        let mut extras = PlayerExtras::default();
+        extras.handle_entity(&moved_to(0.0, 0.0, 0.0), 100, &Clock::new(), &state);
+        assert_eq!(extras.velocity, Vector::default());
+        extras.handle_entity(&moved_to(3.0, -6.0, 1.5), 102, &Clock::new(), &state);
+        assert!((extras.velocity.x - 100.0).abs() < 1e-3);
+        assert!((extras.velocity.y + 200.0).abs() < 1e-3);
+        assert!((extras.velocity.z - 50.0).abs() < 1e-3);
+
+        // only the view changes, so they stood still since the last update
+        let turned = vec![(FLAGS, SendPropValue::Integer(FL_ONGROUND as i64))];
+        let turned = entity(1, 0, UpdateType::Preserve, turned);
+        extras.handle_entity(&turned, 105, &Clock::new(), &state);
+        assert_eq!(extras.velocity, Vector::default());
+        extras.handle_entity(&moved_to(3.0, -3.0, 1.5), 106, &Clock::new(), &state);
+        assert!((extras.velocity.y - 200.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn clip_ammo_and_metal() {
+        let state = parser_state(&["CTFPlayer", "CTFShotgun_Primary", "CTFWrench"]);
+        let mut props = PlayerProps::new();
+        let clock = Clock::new();
+        let shotgun = vec![
+            (ITEM_DEFINITION_INDEX, SendPropValue::Integer(9)),
+            (CLIP, SendPropValue::Integer(6)),
+        ];
+        props.handle_item(
+            20,
+            "CTFShotgun_Primary",
+            &entity(20, 1, UpdateType::Enter, shotgun),
+            &state,
+        );
+        // a wrench has no clip
+        let wrench = vec![(ITEM_DEFINITION_INDEX, SendPropValue::Integer(7))];
+        props.handle_item(
+            21,
+            "CTFWrench",
+            &entity(21, 2, UpdateType::Enter, wrench),
+            &state,
+        );
+        let player = vec![
+            (MY_WEAPONS[0], SendPropValue::Integer(20)),
+            (MY_WEAPONS[1], SendPropValue::Integer(21)),
+            (AMMO[AMMO_PRIMARY], SendPropValue::Integer(32)),
+            (AMMO[AMMO_METAL], SendPropValue::Integer(200)),
+        ];
+        props.handle_player(
+            1,
+            &entity(1, 0, UpdateType::Enter, player),
+            10,
+            &clock,
+            &state,
+        );
+
+        let shot = vec![(CLIP, SendPropValue::Integer(5))];
+        props.handle_item(
+            20,
+            "CTFShotgun_Primary",
+            &entity(20, 1, UpdateType::Preserve, shot),
+            &state,
+        );
+        let built = vec![(AMMO[AMMO_METAL], SendPropValue::Integer(70))];
+        props.handle_player(
+            1,
+            &entity(1, 0, UpdateType::Preserve, built),
+            11,
+            &clock,
+            &state,
+        );
+
+        let extras = props.get(1).unwrap();
+        assert!(extras.is_local());
+        assert_eq!(extras.ammo[AMMO_PRIMARY], Some(32));
+        assert_eq!(extras.ammo[AMMO_SECONDARY], None);
+        assert_eq!(extras.ammo[AMMO_METAL], Some(70));
+        let [primary, _, melee] = props.loadout(extras).map(|slot| props.weapon(slot));
+        let primary = primary.unwrap();
+        assert_eq!((primary.def_index, primary.clip), (Some(9), Some(5)));
+        let melee = melee.unwrap();
+        assert_eq!((melee.def_index, melee.clip), (Some(7), None));
+    }
+
+    #[test]
+    fn conditions_span_words() {
+        let mut conditions = Conditions::default();
+        conditions.0[0] = 1 << cond::URINE;
+        conditions.0[1] = 1 << (cond::INVULNERABLE_USER_BUFF - 32);
+        assert!(conditions.has(cond::URINE));
+        assert!(conditions.ubered());
+        assert!(!conditions.kritzed());
+        assert!(!conditions.has(200));
+    }
+
+    #[test]
+    fn loadout_follows_weapon_class() {
+        let mut props = PlayerProps::new();
+        for (entity, class) in [
+            (10, "CTFKnife"),
+            (11, "CTFRevolver"),
+            (12, "CTFWeaponInvis"),
+        ] {
+            let weapon = Weapon {
+                class: class.to_string(),
+                def_index: None,
+                clip: None,
+            };
+            props.weapons.insert(entity, weapon);
+        }
+        let extras = PlayerExtras {
+            weapons: [Some(12), Some(10), None, Some(11), None, None, None, None],
+            ..PlayerExtras::default()
+        };
+        assert_eq!(props.loadout(&extras), [Some(11), None, Some(10)]);
+        assert_eq!(weapon_slot("CTFShotgun_Primary"), Some(SLOT_PRIMARY));
+        assert_eq!(weapon_slot("CTFShotgun_Soldier"), Some(SLOT_SECONDARY));
+    }
+}
@@ -0,0 +1,148 @@
+use pyo3::prelude::*;
+use tf_demo_parser::demo::header::Header;
+use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::packet::Packet;
+use tf_demo_parser::demo::parser::MessageHandler;
+use tf_demo_parser::{Demo, MessageType, ParserState};
+
+use crate::errors::Result;
+use crate::player::PlayerProps;
+use crate::tracer::{self, Clock, Roster};
+
+/// see if the server in the header is formatted like a hostname, which is
+/// how the client records it. Anyone can edit a header, so on its own this
+/// isn't proof of anything.
+pub fn is_pov_formatted(s: &str) -> bool {
+    if let Some((hostname, port)) = s.split_once(':') {
+        !hostname.is_empty() && port.parse::<u16>().is_ok()
+    } else {
+        false
+    }
+}
+
+/// Whether a demo was recorded from a player's point of view, who recorded
+/// it and what we based that on.
+#[pyclass(get_all)]
+#[derive(Clone)]
+pub struct Pov {
+    /// the demo contains data only the recording client receives
+    pub verdict: bool,
+    pub user_id: Option<u16>,
+    pub steam_id: Option<String>,
+    pub name: Option<String>,
+    /// `Header::server` looks like `host:port`
+    pub server_formatted: bool,
+    /// `Header::nick` matches a player in the roster
+    pub nick_in_roster: bool,
+    /// the demo contains user commands
+    pub has_usercmds: bool,
+    /// some player received the local-only data tables
+    pub has_local_data: bool,
+}
+
+impl Pov {
+    /// Scan a demo for the signs of a POV recording. The whole demo is read,
+    /// as the roster the nick is checked against is only complete at the end.
+    pub fn scan(demo: Demo) -> Result<Self> {
+        let mut has_usercmds = false;
+        let (header, scan) = tracer::scan(demo, PovScan::new(), |packet| {
+            has_usercmds |= matches!(packet, Packet::UserCmd(_));
+            Ok(())
+        })?;
+        Ok(Self::new(&header, &scan, has_usercmds))
+    }
+
+    fn new(header: &Header, scan: &PovScan, has_usercmds: bool) -> Self {
+        let local_player = scan.player_props.local_player();
+        let has_local_data = local_player.is_some();
+        let verdict = has_usercmds || has_local_data;
+
+        let nick_in_roster = scan
+            .roster
+            .roster
+            .iter()
+            .any(|profile| !profile.is_hl_tv && profile.name == header.nick);
+        let recorder = scan
+            .roster
+            .recorder(local_player, has_usercmds, &header.nick);
+        Self {
+            verdict,
+            user_id: recorder.map(|profile| profile.user_id.into()),
+            steam_id: recorder.map(|profile| profile.steam_id.clone()),
+            name: recorder.map(|profile| profile.name.clone()),
+            server_formatted: is_pov_formatted(&header.server),
+            nick_in_roster,
+            has_usercmds,
+            has_local_data,
+        }
+    }
+}
+
+/// Just the parts of a `Tracer` that tell who recorded a demo: the roster,
+/// and the player props that reveal the local player.
+struct PovScan {
+    roster: Roster,
+    player_props: PlayerProps,
+    /// only needed to drive `player_props`, nothing here depends on time
+    clock: Clock,
+}
+
+impl PovScan {
+    fn new() -> Self {
+        Self {
+            roster: Roster::new(),
+            player_props: PlayerProps::new(),
+            clock: Clock::new(),
+        }
+    }
+}
+
+impl MessageHandler for PovScan {
+    type Output = Self;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(message_type, MessageType::PacketEntities) || Roster::does_handle(message_type)
+    }
+
+    fn handle_message(
+        &mut self,
+        message: &Message,
+        tick: tf_demo_parser::demo::data::DemoTick,
+        parser_state: &ParserState,
+    ) {
+        self.roster.handle_message(message, tick, parser_state);
+        self.player_props
+            .handle_message(message, tick.into(), &self.clock, parser_state);
+    }
+
+    fn handle_string_entry(
+        &mut self,
+        table: &str,
+        index: usize,
+        entry: &tf_demo_parser::demo::packet::stringtable::StringTableEntry,
+        parser_state: &ParserState,
+    ) {
+        self.roster
+            .handle_string_entry(table, index, entry, parser_state);
+    }
+
+    fn handle_packet_meta(
+        &mut self,
+        tick: tf_demo_parser::demo::data::DemoTick,
+        meta: &tf_demo_parser::demo::packet::message::MessagePacketMeta,
+        parser_state: &ParserState,
+    ) {
+        self.roster.handle_packet_meta(tick, meta, parser_state);
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self {
+        self
+    }
+}
+
+#[pymethods]
+impl Pov {
+    fn __bool__(&self) -> bool {
+        self.verdict
+    }
+}
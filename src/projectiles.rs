@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tf_demo_parser::demo::message::packetentities::{PacketEntity, UpdateType};
+use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::parser::gamestateanalyser::Player;
+use tf_demo_parser::demo::sendprop::SendPropIdentifier;
+use tf_demo_parser::demo::vector::Vector;
+use tf_demo_parser::ParserState;
+
+use crate::tracer::{handle_to_index, player_entity, Clock, WithTick};
+
+const ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin");
+const ROCKET_ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_TFBaseRocket", "m_vecOrigin");
+const GRENADE_ORIGIN: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFWeaponBaseGrenadeProj", "m_vecOrigin");
+const ROCKET_VELOCITY: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFBaseRocket", "m_vecInitialVelocity");
+const GRENADE_VELOCITY: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFWeaponBaseGrenadeProj", "m_vecVelocity");
+const OWNER: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_hOwnerEntity");
+const THROWER: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFWeaponBaseGrenadeProj", "m_hThrower");
+const PIPE_TYPE: SendPropIdentifier =
+    SendPropIdentifier::new("DT_TFProjectile_Pipebomb", "m_iType");
+
+/// map a server class to the kind of projectile it networks, if any
+fn projectile_kind(class_name: &str) -> Option<&'static str> {
+    match class_name {
+        "CTFProjectile_Rocket" => Some("rocket"),
+        "CTFProjectile_SentryRocket" => Some("sentry_rocket"),
+        "CTFProjectile_EnergyBall" => Some("energy_ball"),
+        "CTFGrenadePipebombProjectile" => Some("pipe"),
+        "CTFProjectile_Arrow" => Some("arrow"),
+        "CTFProjectile_HealingBolt" => Some("healing_bolt"),
+        "CTFProjectile_Flare" => Some("flare"),
+        "CTFProjectile_Syringe" => Some("syringe"),
+        "CTFProjectile_EnergyRing" => Some("energy_ring"),
+        "CTFProjectile_Jar" | "CTFProjectile_JarMilk" | "CTFProjectile_JarGas" => Some("jar"),
+        "CTFProjectile_Cleaver" => Some("cleaver"),
+        "CTFStunBall" | "CTFBall_Ornament" => Some("ball"),
+        _ => None,
+    }
+}
+
+/// pipebomb projectiles share a server class, `m_iType` tells them apart
+fn pipe_kind(pipe_type: i64) -> &'static str {
+    match pipe_type {
+        1 | 2 => "sticky",
+        3 => "cannonball",
+        _ => "pipe",
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProjectileUpdate {
+    pub entity_id: u32,
+    pub kind: String,
+    pub owner: Option<u32>,
+    pub owner_user_id: Option<u16>,
+    pub spawn_tick: u32,
+    pub destroy_tick: Option<u32>,
+    pub position_x: f32,
+    pub position_y: f32,
+    pub position_z: f32,
+    pub velocity_x: f32,
+    pub velocity_y: f32,
+    pub velocity_z: f32,
+}
+
+struct LiveProjectile {
+    serial: u32,
+    kind: &'static str,
+    owner: Option<u32>,
+    spawn_tick: u32,
+    last_tick: u32,
+    position: Option<Vector>,
+    velocity: Option<Vector>,
+    rows: Vec<usize>,
+}
+
+/// Follows projectile entities from spawn to removal, recording one row per
+/// networked update. Velocity comes from the entity when it is networked and
+/// is otherwise differenced from consecutive positions.
+#[derive(Default)]
+pub struct Projectiles {
+    pub updates: Vec<WithTick<ProjectileUpdate>>,
+    live: HashMap<u32, LiveProjectile>,
+}
+
+impl Projectiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_message(
+        &mut self,
+        message: &Message,
+        tick: u32,
+        clock: &Clock,
+        parser_state: &ParserState,
+        players: &[Player],
+    ) {
+        if let Message::PacketEntities(message) = message {
+            for entity in &message.entities {
+                self.handle_entity(entity, tick, clock, parser_state, players);
+            }
+            for id in &message.removed_entities {
+                self.destroy(u32::from(*id), tick);
+            }
+        }
+    }
+
+    fn handle_entity(
+        &mut self,
+        entity: &PacketEntity,
+        tick: u32,
+        clock: &Clock,
+        parser_state: &ParserState,
+        players: &[Player],
+    ) {
+        let id = u32::from(entity.entity_index);
+        if matches!(entity.update_type, UpdateType::Delete) {
+            self.destroy(id, tick);
+            return;
+        }
+        let Some(kind) = parser_state
+            .server_classes
+            .get(usize::from(entity.server_class))
+            .and_then(|class| projectile_kind(class.name.as_str()))
+        else {
+            // the index can be handed to another class without a delete
+            self.destroy(id, tick);
+            return;
+        };
+
+        let respawned = self
+            .live
+            .get(&id)
+            .is_some_and(|live| live.serial != entity.serial_number);
+        if respawned {
+            self.destroy(id, tick);
+        }
+        let live = self.live.entry(id).or_insert_with(|| LiveProjectile {
+            serial: entity.serial_number,
+            kind,
+            owner: None,
+            spawn_tick: tick,
+            last_tick: tick,
+            position: None,
+            velocity: None,
+            rows: Vec::new(),
+        });
+
+        let prev_position = live.position;
+        let mut velocity = None;
+        for prop in entity.props(parser_state) {
+            match prop.identifier {
+                ORIGIN | ROCKET_ORIGIN | GRENADE_ORIGIN => {
+                    if let Ok(position) = Vector::try_from(&prop.value) {
+                        live.position = Some(position);
+                    }
+                }
+                ROCKET_VELOCITY | GRENADE_VELOCITY => {
+                    velocity = Vector::try_from(&prop.value).ok();
+                }
+                OWNER | THROWER => {
+                    if let Some(owner) = i64::try_from(&prop.value).ok().and_then(handle_to_index) {
+                        live.owner = Some(owner);
+                    }
+                }
+                PIPE_TYPE => live.kind = pipe_kind(i64::try_from(&prop.value).unwrap_or_default()),
+                _ => {}
+            }
+        }
+
+        let elapsed = clock.elapsed(live.last_tick, tick);
+        live.velocity = match (velocity, prev_position, live.position) {
+            (Some(velocity), _, _) => Some(velocity),
+            (None, Some(prev), Some(next)) if elapsed > 0.0 => Some(Vector {
+                x: (next.x - prev.x) / elapsed,
+                y: (next.y - prev.y) / elapsed,
+                z: (next.z - prev.z) / elapsed,
+            }),
+            (None, _, _) => live.velocity,
+        };
+        live.last_tick = tick;
+
+        let Some(position) = live.position else {
+            return;
+        };
+        let velocity = live.velocity.unwrap_or_default();
+        let owner_user_id = live.owner.and_then(|owner| {
+            players
+                .iter()
+                .find(|player| player_entity(player) == Some(owner))
+                .and_then(|player| player.info.as_ref())
+                .map(|info| info.user_id.into())
+        });
+        live.rows.push(self.updates.len());
+        let inner = ProjectileUpdate {
+            entity_id: id,
+            kind: live.kind.to_string(),
+            owner: live.owner,
+            owner_user_id,
+            spawn_tick: live.spawn_tick,
+            destroy_tick: None,
+            position_x: position.x,
+            position_y: position.y,
+            position_z: position.z,
+            velocity_x: velocity.x,
+            velocity_y: velocity.y,
+            velocity_z: velocity.z,
+        };
+        self.updates.push(WithTick { inner, tick });
+    }
+
+    fn destroy(&mut self, id: u32, tick: u32) {
+        if let Some(live) = self.live.remove(&id) {
+            for row in live.rows {
+                self.updates[row].inner.destroy_tick = Some(tick);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{entity, parser_state};
+    use tf_demo_parser::demo::message::packetentities::EntityId;
+    use tf_demo_parser::demo::parser::analyser::UserInfo;
+    use tf_demo_parser::demo::sendprop::SendPropValue;
+
+    fn at(x: f32) -> SendPropValue {
+        SendPropValue::Vector(Vector { x, y: 0.0, z: 0.0 })
+    }
+
+    #[test]
+    fn create_update_delete() {
+        let state = parser_state(&["CTFProjectile_Rocket", "CTFAmmoPack"]);
+        let mut owner = Player::default();
+        owner.info = Some(UserInfo {
+            classes: Default::default(),
+            name: "owner".into(),
+            user_id: 7u16.into(),
+            steam_id: "[U:1:1]".into(),
+            entity_id: EntityId::from(5u32),
+            team: Default::default(),
+        });
+        let players = [owner];
+        let clock = Clock::new();
+        let mut projectiles = Projectiles::new();
+        let mut handle = |entity: PacketEntity, tick: u32| {
+            projectiles.handle_entity(&entity, tick, &clock, &state, &players)
+        };
+
+        let spawn = vec![(ROCKET_ORIGIN, at(0.0)), (OWNER, SendPropValue::Integer(5))];
+        handle(entity(100, 0, UpdateType::Enter, spawn), 10);
+        handle(
+            entity(
+                100,
+                0,
+                UpdateType::Preserve,
+                vec![(ROCKET_ORIGIN, at(30.0))],
+            ),
+            12,
+        );
+        // an ammo pack takes the index over without the rocket being deleted
+        handle(entity(100, 1, UpdateType::Enter, Vec::new()), 14);
+        handle(
+            entity(101, 0, UpdateType::Enter, vec![(ROCKET_ORIGIN, at(0.0))]),
+            14,
+        );
+        handle(entity(101, 0, UpdateType::Delete, Vec::new()), 16);
+
+        let rows: Vec<_> = projectiles.updates.iter().map(|row| &row.inner).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].kind, "rocket");
+        assert_eq!(rows[0].owner, Some(5));
+        assert_eq!(rows[0].owner_user_id, Some(7));
+        assert_eq!(rows[1].velocity_x, 1000.0);
+        assert_eq!(rows[0].destroy_tick, Some(14));
+        assert_eq!(rows[1].destroy_tick, Some(14));
+        assert_eq!(rows[2].entity_id, 101);
+        assert_eq!(rows[2].destroy_tick, Some(16));
+        assert!(projectiles.live.is_empty());
+    }
+}
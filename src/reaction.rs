@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::aim::{aim_at, has_usercmds, view_samples, Timeline, ViewSample};
+use crate::columns::LifeState;
+use crate::tracer::{Tracer, WithTick};
+
+/// degrees, the default `fov_desired`, taken as a cone around the crosshair
+pub const FOV: f32 = 90.0;
+/// seconds without damage after which the next hit starts a new engagement
+pub const ENGAGEMENT_GAP: f32 = 3.0;
+/// seconds before the first hit in which we look for the victim becoming visible
+pub const LOOKBACK: f32 = 5.0;
+/// degrees per second the view has to close in on the victim to count as aiming at them
+pub const AIM_SPEED: f32 = 30.0;
+
+pub struct EngagementConfig {
+    pub fov: f32,
+    pub gap: f32,
+    pub lookback: f32,
+}
+
+impl Default for EngagementConfig {
+    fn default() -> Self {
+        Self {
+            fov: FOV,
+            gap: ENGAGEMENT_GAP,
+            lookback: LOOKBACK,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Engagement {
+    pub attacker: u16,
+    pub victim: u16,
+    pub attacker_key: u32,
+    pub victim_key: u32,
+    pub visible_tick: Option<u32>,
+    pub aim_tick: Option<u32>,
+    pub reaction_time: Option<f32>,
+    pub time_to_damage: Option<f32>,
+    pub hits: u32,
+    pub damage: u32,
+    pub last_hit_tick: u32,
+    pub from_usercmd: bool,
+    /// whether the victim had to be in PVS to count as visible. PVS is
+    /// relative to whoever recorded the demo, so this is only done when they
+    /// are the attacker; otherwise visible just means within their FOV,
+    /// walls and all
+    pub pvs_checked: bool,
+}
+
+/// whether a target `offset` degrees from the view direction is within
+/// `fov`, which we treat as a cone of half-angle `fov / 2` around the
+/// crosshair rather than the wider horizontal extent of the screen
+pub fn in_fov(offset: f32, fov: f32) -> bool {
+    offset <= fov / 2.0
+}
+
+/// Group the damage an attacker deals to a victim into engagements and
+/// estimate, for each, when the victim became visible to the attacker
+/// (within their field of view and, if they recorded the demo, in PVS), when
+/// the attacker started aiming at them and when the first damage landed. The
+/// tick of an engagement is its first hit. Damage by or to a user id that
+/// isn't in the roster at the time is left out.
+pub fn engagements(tracer: &Tracer, config: &EngagementConfig) -> Vec<WithTick<Engagement>> {
+    let timeline = Timeline::new(&tracer.states);
+    let interval = tracer.clock.interval_per_tick;
+    let gap = (config.gap / interval) as u32;
+    let lookback = (config.lookback / interval) as u32;
+
+    let mut engagements: Vec<WithTick<Engagement>> = Vec::new();
+    let mut open: HashMap<(u32, u32), usize> = HashMap::new();
+    let mut views: HashMap<u32, Vec<ViewSample>> = HashMap::new();
+    for event in &tracer.events {
+        let (attacker, victim) = (event.inner.attacker, event.inner.user_id);
+        if attacker == 0 || attacker == victim {
+            continue;
+        }
+        let keys = (
+            tracer.roster.key_at(attacker, event.tick),
+            tracer.roster.key_at(victim, event.tick),
+        );
+        let (Some(attacker_key), Some(victim_key)) = keys else {
+            continue;
+        };
+        if let Some(&index) = open.get(&(attacker_key, victim_key)) {
+            let engagement = &mut engagements[index].inner;
+            if event.tick.saturating_sub(engagement.last_hit_tick) <= gap {
+                engagement.hits += 1;
+                engagement.damage += u32::from(event.inner.damage_amount);
+                engagement.last_hit_tick = event.tick;
+                continue;
+            }
+        }
+
+        let pvs_checked = tracer.pov_key == Some(attacker_key);
+        let attacker_views = views
+            .entry(attacker_key)
+            .or_insert_with(|| view_samples(tracer, &timeline, attacker_key));
+        let start = attacker_views.partition_point(|view| view.tick + lookback < event.tick);
+        let end = attacker_views.partition_point(|view| view.tick <= event.tick);
+        let window = &attacker_views[start..end];
+        let aim = |view: ViewSample| {
+            let (tick, pitch, yaw) = (view.tick, view.pitch, view.yaw);
+            aim_at(&timeline, attacker_key, victim_key, tick, pitch, yaw)
+        };
+        let visible: Vec<bool> = window
+            .iter()
+            .map(|view| {
+                let in_view = aim(*view).is_some_and(|target| in_fov(target.offset(), config.fov));
+                let in_pvs = timeline.at(victim_key, view.tick).is_some_and(|state| {
+                    (*state.in_pvs || !pvs_checked) && *state.state == LifeState::Alive
+                });
+                in_view && in_pvs
+            })
+            .collect();
+
+        // the start of the last run of visibility before the hit
+        let visible_from = visible.iter().rposition(|visible| *visible).map(|last| {
+            visible[..=last]
+                .iter()
+                .rposition(|visible| !*visible)
+                .map_or(0, |hidden| hidden + 1)
+        });
+        let aim_tick = visible_from.and_then(|from| {
+            window[from..].windows(2).find_map(|pair| {
+                let [prev, next] = [pair[0], pair[1]];
+                let (before, after) = (aim(prev)?, aim(next)?);
+                let dt = tracer.clock.elapsed(prev.tick, next.tick);
+                let closing = (before.offset() - after.offset()) / dt;
+                (dt > 0.0 && closing >= AIM_SPEED).then_some(prev.tick)
+            })
+        });
+        let visible_tick = visible_from.map(|from| window[from].tick);
+        let elapsed = |from: u32, to: u32| tracer.clock.elapsed(from, to);
+
+        open.insert((attacker_key, victim_key), engagements.len());
+        let inner = Engagement {
+            attacker,
+            victim,
+            attacker_key,
+            victim_key,
+            visible_tick,
+            aim_tick,
+            reaction_time: visible_tick
+                .zip(aim_tick)
+                .map(|(from, to)| elapsed(from, to)),
+            time_to_damage: visible_tick.map(|from| elapsed(from, event.tick)),
+            hits: 1,
+            damage: u32::from(event.inner.damage_amount),
+            last_hit_tick: event.tick,
+            from_usercmd: has_usercmds(tracer, attacker_key),
+            pvs_checked,
+        };
+        let tick = event.tick;
+        engagements.push(WithTick { inner, tick });
+    }
+    engagements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aim::{angle_between, view_direction};
+    use crate::testing::{profile, snapshot};
+    use tf_demo_parser::demo::gameevent_gen::PlayerHurtEvent;
+    use tf_demo_parser::demo::parser::gamestateanalyser::Team;
+
+    fn hurt(tick: u32, attacker: u16, victim: u16) -> WithTick<PlayerHurtEvent> {
+        let inner = PlayerHurtEvent {
+            user_id: victim,
+            health: 100,
+            attacker,
+            damage_amount: 50,
+            custom: 0,
+            show_disguised_crit: false,
+            crit: false,
+            mini_crit: false,
+            all_see_crit: false,
+            weapon_id: 0,
+            bonus_effect: 0,
+        };
+        WithTick { inner, tick }
+    }
+
+    /// a red player turning onto a blu player 240 units east of them, who
+    /// comes into their FOV at tick 105 and is closed in on from tick 106
+    fn tracer() -> Tracer {
+        let mut tracer = Tracer::new();
+        tracer.roster.roster = vec![profile(0, 2, 0), profile(1, 3, 0)];
+        let victim = snapshot(100, 1, 3, Team::Blue, [240.0, 0.0], [0.0, 180.0]);
+        tracer.states.push(victim);
+        let yaws = [
+            90.0, 90.0, 90.0, 90.0, 90.0, 40.0, 40.0, 20.0, 0.0, 0.0, 0.0,
+        ];
+        for (tick, yaw) in (100..).zip(yaws) {
+            let view = snapshot(tick, 0, 2, Team::Red, [0.0, 0.0], [0.0, yaw]);
+            tracer.states.push(view);
+        }
+        tracer.events = vec![
+            hurt(110, 2, 3),
+            // world damage, self damage and unknown players are left out
+            hurt(112, 0, 3),
+            hurt(114, 2, 2),
+            hurt(116, 9, 3),
+            // within ENGAGEMENT_GAP of the last hit
+            hurt(300, 2, 3),
+            hurt(600, 2, 3),
+        ];
+        tracer
+    }
+
+    #[test]
+    fn fov_is_a_cone_of_half_the_angle() {
+        let crosshair = view_direction(0.0, 0.0);
+        // 44 degrees right and 44 degrees up are both inside a 90 degree fov,
+        // but together they're further than 45 degrees from the crosshair
+        let right = angle_between(crosshair, view_direction(0.0, 44.0));
+        let up = angle_between(crosshair, view_direction(-44.0, 0.0));
+        let corner = angle_between(crosshair, view_direction(-44.0, 44.0));
+        assert!(in_fov(right, FOV));
+        assert!(in_fov(up, FOV));
+        assert!(!in_fov(corner, FOV));
+        assert!(!in_fov(
+            angle_between(crosshair, view_direction(0.0, 46.0)),
+            FOV
+        ));
+    }
+
+    #[test]
+    fn hits_within_the_gap_are_one_engagement() {
+        let engagements = engagements(&tracer(), &EngagementConfig::default());
+        let summary: Vec<(u32, u32, u32, u32)> = engagements
+            .iter()
+            .map(|engagement| {
+                let inner = &engagement.inner;
+                (
+                    engagement.tick,
+                    inner.hits,
+                    inner.damage,
+                    inner.last_hit_tick,
+                )
+            })
+            .collect();
+        assert_eq!(summary, [(110, 2, 100, 300), (600, 1, 50, 600)]);
+        let first = &engagements[0].inner;
+        assert_eq!((first.attacker, first.victim), (2, 3));
+        assert_eq!((first.attacker_key, first.victim_key), (0, 1));
+    }
+
+    #[test]
+    fn visible_and_aim_ticks() {
+        let engagements = engagements(&tracer(), &EngagementConfig::default());
+        let first = &engagements[0].inner;
+        assert!(!first.pvs_checked && !first.from_usercmd);
+        assert_eq!(first.visible_tick, Some(105));
+        assert_eq!(first.aim_tick, Some(106));
+        assert!((first.reaction_time.unwrap() - 0.015).abs() < 1e-6);
+        assert!((first.time_to_damage.unwrap() - 5.0 * 0.015).abs() < 1e-6);
+        // no views in the lookback before the second engagement
+        let second = &engagements[1].inner;
+        assert_eq!((second.visible_tick, second.aim_tick), (None, None));
+        assert_eq!(second.reaction_time, None);
+    }
+
+    #[test]
+    fn pvs_is_checked_for_the_recorder() {
+        let mut tracer = tracer();
+        tracer.pov_key = Some(0);
+        let first = engagements(&tracer, &EngagementConfig::default())[0]
+            .inner
+            .clone();
+        // the victim was never in the recorder's PVS
+        assert!(first.pvs_checked);
+        assert_eq!((first.visible_tick, first.aim_tick), (None, None));
+
+        tracer.pov_key = Some(1);
+        let first = engagements(&tracer, &EngagementConfig::default())[0]
+            .inner
+            .clone();
+        assert!(!first.pvs_checked);
+        assert_eq!(first.visible_tick, Some(105));
+    }
+}
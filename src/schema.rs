@@ -0,0 +1,197 @@
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_arrow::schema::TracingOptions;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use tf_demo_parser::demo::gameevent_gen::PlayerHurtEvent;
+use tf_demo_parser::demo::parser::gamestateanalyser::World;
+
+use crate::aim::{AimSample, CrosshairSample};
+use crate::errors::{Error, Result};
+use crate::movement::MovementFeatures;
+use crate::pauses::Pause;
+use crate::projectiles::ProjectileUpdate;
+use crate::reaction::Engagement;
+use crate::serialize::to_polars;
+use crate::server::ConVarChange;
+use crate::sounds::Sound;
+use crate::tracer::{
+    Clock, ConsoleCommand, NameChange, Presence, Profile, States, Warning, WithTick,
+};
+use crate::usercmd::UserCommand;
+
+/// Bump whenever a column is added, removed, renamed or changes type in any
+/// of the tables below.
+pub const SCHEMA_VERSION: u32 = 2;
+
+pub const TABLES: &[&str] = &[
+    "states",
+    "events",
+    "roster",
+    "name_history",
+    "presence",
+    "warnings",
+    "bounds",
+    "projectiles",
+    "usercmds",
+    "console",
+    "convars",
+    "pauses",
+    "sounds",
+    "aim_features",
+    "crosshair",
+    "engagements",
+    "movement_features",
+];
+
+pub fn tracing_options() -> TracingOptions {
+    TracingOptions::default()
+        .allow_null_fields(true)
+        .string_dictionary_encoding(false)
+        .enums_without_data_as_strings(true)
+}
+
+/// The columns of every table as of `SCHEMA_VERSION`, checked in so that
+/// any change to them shows up in review. Regenerate it with
+/// `cargo test write_schema -- --ignored` after bumping the version.
+pub const EMBEDDED: &str = include_str!("schema.json");
+
+#[derive(Serialize, Deserialize)]
+struct Embedded {
+    version: u32,
+    tables: BTreeMap<String, Vec<Field>>,
+}
+
+fn embedded() -> &'static Embedded {
+    static EMBEDDED_SCHEMA: OnceLock<Embedded> = OnceLock::new();
+    EMBEDDED_SCHEMA.get_or_init(|| serde_json::from_str(EMBEDDED).expect("schema.json is valid"))
+}
+
+/// Every table is pinned to its embedded columns, since whatever the types
+/// behind it serialise to may change, e.g. when we upgrade tf-demo-parser.
+fn pinned(table: &str) -> Option<Schema> {
+    let fields = embedded().tables.get(table)?;
+    Some(Schema::from_iter(fields.iter().cloned()))
+}
+
+/// Select, order and cast the columns of `table` to its pinned schema.
+/// Columns that aren't pinned are dropped, and missing ones are an error
+/// rather than a column of nulls.
+pub fn conform(table: &str, frame: DataFrame) -> Result<DataFrame> {
+    let Some(schema) = pinned(table) else {
+        return Err(Error::UnknownTable(table.to_string()));
+    };
+    let columns = schema
+        .iter()
+        .map(|(name, dtype)| match frame.column(name.as_str()) {
+            Ok(column) => Ok(column.cast(dtype)?),
+            Err(_) => Err(Error::MissingColumn {
+                table: table.to_string(),
+                column: name.to_string(),
+            }),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(DataFrame::new(columns)?)
+}
+
+/// Add the time columns to a table and conform it to its pinned schema.
+pub fn finish(table: &str, frame: DataFrame, clock: &Clock) -> Result<DataFrame> {
+    conform(table, clock.with_time(frame)?)
+}
+
+/// An empty frame with the columns of `table`.
+pub fn empty(table: &str) -> Result<DataFrame> {
+    conform(table, traced(table)?)
+}
+
+/// An empty frame with the columns the types behind `table` serialise to,
+/// which should be the ones in `EMBEDDED`.
+fn traced(table: &str) -> Result<DataFrame> {
+    let tropt = Some(tracing_options());
+    let frame = match table {
+        "states" => States::default().to_polars()?,
+        "events" => WithTick::<PlayerHurtEvent>::to_polars(std::iter::empty(), tropt)?,
+        "roster" => to_polars::<Profile>(&[], tropt)?,
+        "name_history" => WithTick::<NameChange>::to_polars(std::iter::empty(), tropt)?,
+        "presence" => to_polars::<Presence>(&[], tropt)?,
+        "warnings" => WithTick::<Warning>::to_polars(std::iter::empty(), tropt)?,
+        "bounds" => WithTick::<World>::to_polars(std::iter::empty(), tropt)?,
+        "projectiles" => WithTick::<ProjectileUpdate>::to_polars(std::iter::empty(), tropt)?,
+        "usercmds" => WithTick::<UserCommand>::to_polars(std::iter::empty(), tropt)?,
+        "console" => WithTick::<ConsoleCommand>::to_polars(std::iter::empty(), tropt)?,
+        "convars" => WithTick::<ConVarChange>::to_polars(std::iter::empty(), tropt)?,
+        "pauses" => Pause::to_polars(&[], tropt)?,
+        "sounds" => WithTick::<Sound>::to_polars(std::iter::empty(), tropt)?,
+        "aim_features" => WithTick::<AimSample>::to_polars(std::iter::empty(), tropt)?,
+        "crosshair" => WithTick::<CrosshairSample>::to_polars(std::iter::empty(), tropt)?,
+        "engagements" => WithTick::<Engagement>::to_polars(std::iter::empty(), tropt)?,
+        "movement_features" => to_polars::<MovementFeatures>(&[], tropt)?,
+        _ => return Err(Error::UnknownTable(table.to_string())),
+    };
+    Clock::new().with_time(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current() -> Embedded {
+        let tables = TABLES.iter().map(|&table| {
+            let fields = traced(table).unwrap().schema().iter_fields().collect();
+            (table.to_string(), fields)
+        });
+        Embedded {
+            version: SCHEMA_VERSION,
+            tables: tables.collect(),
+        }
+    }
+
+    #[test]
+    #[ignore = "rewrites src/schema.json"]
+    fn write_schema() {
+        let json = serde_json::to_string_pretty(&current()).unwrap();
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/schema.json");
+        std::fs::write(path, json + "\n").unwrap();
+    }
+
+    #[test]
+    fn embedded_schema_is_current() {
+        let current = current();
+        assert_eq!(embedded().version, SCHEMA_VERSION, "regenerate schema.json");
+        for table in TABLES {
+            let fields = &current.tables[*table];
+            assert_eq!(&embedded().tables[*table], fields, "{table} changed");
+            let frame = empty(table).unwrap();
+            assert_eq!(frame.height(), 0);
+            assert_eq!(&frame.schema().iter_fields().collect::<Vec<_>>(), fields);
+        }
+        assert!(matches!(empty("nope"), Err(Error::UnknownTable(_))));
+    }
+
+    #[test]
+    fn conform_orders_casts_and_rejects_missing_columns() {
+        let frame = df! {
+            "disconnect_tick" => [None::<u64>],
+            "last_seen" => [20u64],
+            "extra" => [true],
+            "first_seen" => [10u64],
+            "name" => ["bot"],
+            "steam_id" => ["BOT"],
+            "user_id" => [2u64],
+            "key" => [0u64],
+        }
+        .unwrap();
+        let conformed = conform("presence", frame.clone()).unwrap();
+        assert_eq!(conformed.schema(), empty("presence").unwrap().schema());
+        assert_eq!(
+            conformed.column("last_seen").unwrap().u32().unwrap().get(0),
+            Some(20)
+        );
+
+        let missing = frame.drop("name").unwrap();
+        assert!(matches!(
+            conform("presence", missing),
+            Err(Error::MissingColumn { column, .. }) if column == "name"
+        ));
+    }
+}
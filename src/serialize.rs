@@ -23,7 +23,7 @@ pub fn json_to_py<'py>(py: Python<'py>, v: &serde_json::Value) -> Result<PyObjec
             Ok(dict.into())
         }
         Value::Array(arr) => {
-            let istrm: Result<Vec<_>> = arr.into_iter().map(|x| json_to_py(py, x)).collect();
+            let istrm: Result<Vec<_>> = arr.iter().map(|x| json_to_py(py, x)).collect();
             Ok(PyList::new_bound(py, istrm?).into())
         }
         Value::Null => Ok(py.None()),
@@ -41,9 +41,9 @@ pub fn json_to_py<'py>(py: Python<'py>, v: &serde_json::Value) -> Result<PyObjec
     }
 }
 
-pub fn json_match<'v>(
+pub fn json_match(
     json_path: Option<&JsonPath>,
-    payload: &'v serde_json::Value,
+    payload: &serde_json::Value,
 ) -> Option<serde_json::Value> {
     if let Some(path) = json_path {
         let values = path
@@ -61,24 +61,23 @@ pub fn json_match<'v>(
         Some(payload.clone())
     }
 }
+/// Columns are traced from the type rather than the values, so an empty
+/// slice still gives a frame with the full schema.
 pub fn to_polars<T: Serialize + for<'de> Deserialize<'de>>(
     values: &[T],
     config: Option<TracingOptions>,
-) -> Result<Option<DataFrame>> {
-    if values.is_empty() {
-        return Ok(None);
-    }
-    let tracing_options = config.unwrap_or_else(TracingOptions::default);
+) -> Result<DataFrame> {
+    let tracing_options = config.unwrap_or_default();
     let fields = Vec::<FieldRef>::from_type::<T>(tracing_options)?;
     let arrays: Vec<ArrayRef> = serde_arrow::to_arrow(&fields, values)?;
 
     let mut series_vec = Vec::new();
     for (field, array) in fields.iter().zip(arrays) {
-        let series_name = field.name().as_str().into();
+        let series_name = field.name().as_str();
         let series = Series::from_arrow_chunks(series_name, vec![array.into()])?;
         series_vec.push(series);
     }
 
     let df: polars::prelude::DataFrame = DataFrame::new(series_vec)?;
-    Ok(Some(df))
+    Ok(df)
 }
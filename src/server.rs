@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use tf_demo_parser::demo::message::{Message, ServerInfoMessage};
+
+use crate::tracer::WithTick;
+
+/// What the server told us about itself when we connected.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServerDetails {
+    pub server_name: String,
+    pub map: String,
+    /// hex digest of the map file, to tell apart versions of the same map
+    pub map_hash: String,
+    pub game: String,
+    pub version: u16,
+    pub platform: String,
+    pub interval_per_tick: f32,
+    pub tick_rate: f32,
+    pub max_players: u8,
+    pub player_slot: u8,
+    pub dedicated: bool,
+    pub stv: bool,
+    pub replay: bool,
+}
+
+impl ServerDetails {
+    pub fn new(info: &ServerInfoMessage) -> Self {
+        Self {
+            server_name: info.server_name.clone(),
+            map: info.map.clone(),
+            map_hash: info
+                .map_hash
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            game: info.game.clone(),
+            version: info.version,
+            platform: info.platform.clone(),
+            interval_per_tick: info.interval_per_tick,
+            tick_rate: 1.0 / info.interval_per_tick,
+            max_players: info.max_player_count,
+            player_slot: info.player_slot,
+            dedicated: info.dedicated,
+            stv: info.stv,
+            replay: info.replay,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConVarChange {
+    pub name: String,
+    pub value: String,
+}
+
+/// Server info and every replicated convar, including the ones set before
+/// the first tick.
+#[derive(Default)]
+pub struct Server {
+    pub details: Option<ServerDetails>,
+    pub convars: Vec<WithTick<ConVarChange>>,
+}
+
+impl Server {
+    pub fn handle_message(&mut self, message: &Message, tick: u32) {
+        match message {
+            Message::ServerInfo(info) => self.details = Some(ServerDetails::new(info)),
+            Message::SetConVar(message) => {
+                for var in &message.vars {
+                    let inner = ConVarChange {
+                        name: var.key.clone(),
+                        value: var.value.clone(),
+                    };
+                    self.convars.push(WithTick { inner, tick });
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::packet::stringtable::StringTableEntry;
+use tf_demo_parser::Stream;
+
+use crate::errors::Result;
+use crate::tracer::WithTick;
+
+/// `MAX_EDICT_BITS`
+const EDICT_BITS: usize = 11;
+/// entities below 32 are sent in this many bits instead
+const SHORT_EDICT_BITS: usize = 5;
+/// `MAX_SOUND_INDEX_BITS`
+const SOUND_INDEX_BITS: usize = 14;
+/// `SND_FLAG_BITS_ENCODE`
+const FLAG_BITS: usize = 11;
+/// `SOUND_SEQNUMBER_BITS`
+const SEQUENCE_BITS: usize = 10;
+/// `MAX_SNDLVL_BITS`
+const SOUND_LEVEL_BITS: usize = 9;
+/// `MAX_SOUND_DELAY_MSEC_ENCODE_BITS`
+const DELAY_BITS: usize = 13;
+/// `COORD_INTEGER_BITS - 2`, origins are sent in steps of `ORIGIN_SCALE`
+const ORIGIN_BITS: usize = 12;
+const ORIGIN_SCALE: f32 = 8.0;
+/// `SND_STOP`, a sound with only this flag set sends nothing after it
+const SND_STOP: u32 = 1 << 2;
+/// `CHAN_STATIC`
+const CHANNEL_STATIC: u8 = 6;
+
+/// A sound the server started or stopped.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Sound {
+    pub entity: u16,
+    pub sound_index: u16,
+    /// path from the `soundprecache` table, `None` for sentences
+    pub sound: Option<String>,
+    pub channel: u8,
+    pub volume: f32,
+    /// attenuation in dB, sounds carry further the higher it is
+    pub sound_level: u16,
+    pub pitch: u8,
+    pub origin_x: f32,
+    pub origin_y: f32,
+    pub origin_z: f32,
+    /// stops whatever `entity` is playing on `channel`, nothing else is sent
+    pub stop: bool,
+}
+
+/// The fields of `SoundInfo_t` that go over the wire, which each sound in a
+/// message is a delta against the previous one of.
+#[derive(Clone, Debug, PartialEq)]
+struct SoundInfo {
+    entity: u16,
+    sound_index: u16,
+    flags: u32,
+    channel: u8,
+    sentence: bool,
+    sequence: u16,
+    volume: f32,
+    sound_level: u16,
+    pitch: u8,
+    origin: [f32; 3],
+}
+
+impl Default for SoundInfo {
+    /// `SoundInfo_t::SetDefault`
+    fn default() -> Self {
+        Self {
+            entity: 0,
+            sound_index: 0,
+            flags: 0,
+            channel: CHANNEL_STATIC,
+            sentence: false,
+            sequence: 0,
+            volume: 1.0,
+            sound_level: 75,
+            pitch: 100,
+            origin: [0.0; 3],
+        }
+    }
+}
+
+/// `ReadSBitLong`
+fn read_signed(stream: &mut Stream, bits: usize) -> Result<i32> {
+    let raw: u32 = stream.read_int(bits)?;
+    let shift = 32 - bits;
+    Ok(((raw << shift) as i32) >> shift)
+}
+
+impl SoundInfo {
+    /// `SoundInfo_t::ReadDelta` as of protocol 24, fields that aren't sent
+    /// keep their value from `self`
+    fn read_delta(&self, stream: &mut Stream) -> Result<Self> {
+        let mut sound = self.clone();
+        if stream.read_bool()? {
+            let bits = match stream.read_bool()? {
+                true => SHORT_EDICT_BITS,
+                false => EDICT_BITS,
+            };
+            sound.entity = stream.read_int(bits)?;
+        }
+        if stream.read_bool()? {
+            sound.sound_index = stream.read_int(SOUND_INDEX_BITS)?;
+        }
+        if stream.read_bool()? {
+            sound.flags = stream.read_int(FLAG_BITS)?;
+        }
+        if stream.read_bool()? {
+            sound.channel = stream.read_int(3)?;
+        }
+        let _ambient = stream.read_bool()?;
+        sound.sentence = stream.read_bool()?;
+        if sound.flags == SND_STOP {
+            // `SoundInfo_t::ClearStopFields`
+            sound.sequence = 0;
+            sound.volume = 0.0;
+            sound.sound_level = 0;
+            sound.pitch = 100;
+            sound.origin = [0.0; 3];
+            return Ok(sound);
+        }
+        if !stream.read_bool()? {
+            sound.sequence = match stream.read_bool()? {
+                true => (self.sequence + 1) & ((1 << SEQUENCE_BITS) - 1),
+                false => stream.read_int(SEQUENCE_BITS)?,
+            };
+        }
+        if stream.read_bool()? {
+            sound.volume = f32::from(stream.read_int::<u8>(7)?) / 127.0;
+        }
+        if stream.read_bool()? {
+            sound.sound_level = stream.read_int(SOUND_LEVEL_BITS)?;
+        }
+        if stream.read_bool()? {
+            sound.pitch = stream.read_int(8)?;
+        }
+        if stream.read_bool()? {
+            // the delay, which only matters for mixing
+            read_signed(stream, DELAY_BITS)?;
+        }
+        for axis in &mut sound.origin {
+            if stream.read_bool()? {
+                *axis = read_signed(stream, ORIGIN_BITS)? as f32 * ORIGIN_SCALE;
+            }
+        }
+        if stream.read_bool()? {
+            // the speaker entity, for sounds played through a speaker
+            read_signed(stream, EDICT_BITS + 1)?;
+        }
+        Ok(sound)
+    }
+}
+
+/// Every sound in `ParseSounds` messages, with names from `soundprecache`.
+#[derive(Default)]
+pub struct Sounds {
+    pub sounds: Vec<WithTick<Sound>>,
+    /// `soundprecache` entries by index
+    names: Vec<Option<String>>,
+}
+
+impl Sounds {
+    pub fn handle_message(&mut self, message: &Message, tick: u32) {
+        let Message::ParseSounds(message) = message else {
+            return;
+        };
+        let mut stream = message.data.clone();
+        let count = if message.reliable { 1 } else { message.num };
+        let mut previous = SoundInfo::default();
+        for _ in 0..count {
+            // every sound is a delta against the last, so there's no picking
+            // up after a bad one
+            let Ok(sound) = previous.read_delta(&mut stream) else {
+                break;
+            };
+            let inner = self.resolve(&sound);
+            self.sounds.push(WithTick { inner, tick });
+            previous = sound;
+        }
+    }
+
+    pub fn handle_string_entry(&mut self, table: &str, index: usize, entry: &StringTableEntry) {
+        if table != "soundprecache" {
+            return;
+        }
+        if self.names.len() <= index {
+            self.names.resize(index + 1, None);
+        }
+        let text: Option<&str> = entry.text.as_ref().map(AsRef::as_ref);
+        self.names[index] = text.map(str::to_string);
+    }
+
+    fn resolve(&self, sound: &SoundInfo) -> Sound {
+        let name = match sound.sentence {
+            true => None,
+            false => self.names.get(usize::from(sound.sound_index)).cloned(),
+        };
+        let [origin_x, origin_y, origin_z] = sound.origin;
+        Sound {
+            entity: sound.entity,
+            sound_index: sound.sound_index,
+            sound: name.flatten(),
+            channel: sound.channel,
+            volume: sound.volume,
+            sound_level: sound.sound_level,
+            pitch: sound.pitch,
+            origin_x,
+            origin_y,
+            origin_z,
+            stop: sound.flags == SND_STOP,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitbuffer::{BitReadBuffer, BitReadStream, BitWriteStream, LittleEndian};
+
+    #[test]
+    fn deltas_against_the_previous_sound() {
+        let mut data = Vec::new();
+        let mut writer = BitWriteStream::new(&mut data, LittleEndian);
+        // entity 3 in the short form, sound 7, everything else unchanged
+        writer.write_bool(true).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_int(3u32, SHORT_EDICT_BITS).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_int(7u32, SOUND_INDEX_BITS).unwrap();
+        writer.write_int(0u32, 4).unwrap();
+        // same sequence, half volume, no level, pitch or delay
+        writer.write_bool(true).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_int(64u32, 7).unwrap();
+        writer.write_int(0u32, 3).unwrap();
+        // x = -2 * 8, y and z unchanged, no speaker
+        writer.write_bool(true).unwrap();
+        writer.write_int(0xffeu32, ORIGIN_BITS).unwrap();
+        writer.write_int(0u32, 3).unwrap();
+        // the same entity stops the sound
+        writer.write_bool(false).unwrap();
+        writer.write_bool(false).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_int(SND_STOP, FLAG_BITS).unwrap();
+        writer.write_int(0u32, 3).unwrap();
+
+        let mut stream = BitReadStream::new(BitReadBuffer::new_owned(data, LittleEndian));
+        let first = SoundInfo::default().read_delta(&mut stream).unwrap();
+        assert_eq!(first.entity, 3);
+        assert_eq!(first.sound_index, 7);
+        assert_eq!(first.volume, 64.0 / 127.0);
+        assert_eq!(first.origin, [-16.0, 0.0, 0.0]);
+        let second = first.read_delta(&mut stream).unwrap();
+        assert_eq!((second.entity, second.sound_index), (3, 7));
+        assert_eq!(second.flags, SND_STOP);
+        assert_eq!(second.volume, 0.0);
+    }
+}
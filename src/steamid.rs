@@ -0,0 +1,105 @@
+/// `k_EAccountTypeIndividual`, the only account type players connect with
+const ACCOUNT_TYPE_INDIVIDUAL: u64 = 1;
+/// the desktop instance individual accounts use
+const INSTANCE_DESKTOP: u64 = 1;
+const UNIVERSE_PUBLIC: u8 = 1;
+
+/// An individual Steam account, parsed from any of the textual forms
+/// servers report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SteamId {
+    pub account_id: u32,
+    pub universe: u8,
+}
+
+impl SteamId {
+    /// parse `[U:1:N]`, `STEAM_X:Y:Z` or a decimal SteamID64, returning
+    /// `None` for bots and anything else
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(id3) = s.strip_prefix("[U:").and_then(|s| s.strip_suffix(']')) {
+            let (universe, account_id) = id3.split_once(':')?;
+            return Some(Self {
+                account_id: account_id.parse().ok()?,
+                universe: universe.parse().ok()?,
+            });
+        }
+        if let Some(id2) = s.strip_prefix("STEAM_") {
+            let mut parts = id2.splitn(3, ':');
+            let universe: u8 = parts.next()?.parse().ok()?;
+            let low: u32 = parts.next()?.parse().ok()?;
+            let high: u32 = parts.next()?.parse().ok()?;
+            if low > 1 {
+                return None;
+            }
+            return Some(Self {
+                account_id: high.checked_mul(2)?.checked_add(low)?,
+                // old engines, TF2 included, print the public universe as 0
+                universe: universe.max(UNIVERSE_PUBLIC),
+            });
+        }
+        let id64: u64 = s.parse().ok()?;
+        if (id64 >> 52) & 0xF != ACCOUNT_TYPE_INDIVIDUAL {
+            return None;
+        }
+        Some(Self {
+            account_id: id64 as u32,
+            universe: (id64 >> 56) as u8,
+        })
+    }
+
+    pub fn steam_id64(&self) -> u64 {
+        (u64::from(self.universe) << 56)
+            | (ACCOUNT_TYPE_INDIVIDUAL << 52)
+            | (INSTANCE_DESKTOP << 32)
+            | u64::from(self.account_id)
+    }
+
+    /// `[U:1:N]`
+    pub fn steam_id3(&self) -> String {
+        format!("[U:{}:{}]", self.universe, self.account_id)
+    }
+
+    /// legacy `STEAM_0:X:Y`, as TF2 prints it in `status`
+    pub fn steam_id2(&self) -> String {
+        format!("STEAM_0:{}:{}", self.account_id & 1, self.account_id >> 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SteamID64 of the first individual account in the public universe
+    const INDIVIDUAL_BASE: u64 = 76561197960265728;
+
+    #[test]
+    fn formats_agree() {
+        let account_id = 22202;
+        for s in [
+            "[U:1:22202]",
+            "STEAM_0:0:11101",
+            "STEAM_1:0:11101",
+            "76561197960287930",
+        ] {
+            let id = SteamId::parse(s).unwrap();
+            assert_eq!(
+                id,
+                SteamId {
+                    account_id,
+                    universe: 1
+                }
+            );
+            assert_eq!(id.steam_id64(), INDIVIDUAL_BASE + u64::from(account_id));
+            assert_eq!(id.steam_id3(), "[U:1:22202]");
+            assert_eq!(id.steam_id2(), "STEAM_0:0:11101");
+        }
+    }
+
+    #[test]
+    fn rejects_bots_and_garbage() {
+        for s in ["BOT", "", "[U:1:]", "STEAM_0:2:1", "[G:1:4]", "12345"] {
+            assert_eq!(SteamId::parse(s), None, "{s}");
+        }
+    }
+}
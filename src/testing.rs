@@ -0,0 +1,87 @@
+//! Builders for the parser types that tests feed to message handlers.
+
+use tf_demo_parser::demo::data::userinfo::PlayerInfo;
+use tf_demo_parser::demo::message::packetentities::{EntityId, PacketEntity, UpdateType};
+use tf_demo_parser::demo::packet::datatable::{ClassId, SendTable, ServerClass};
+use tf_demo_parser::demo::parser::gamestateanalyser::{Class, Player, Team};
+use tf_demo_parser::demo::sendprop::{SendProp, SendPropIdentifier, SendPropValue};
+use tf_demo_parser::ParserState;
+
+use crate::player::PlayerProps;
+use crate::tracer::{Profile, Roster, Snapshot, WithTick};
+
+/// a parser state knowing the server classes `classes`, with ids in order
+pub fn parser_state(classes: &[&str]) -> ParserState {
+    let mut state = ParserState::new(24, |_| true, false);
+    for (id, name) in classes.iter().enumerate() {
+        state.server_classes.push(ServerClass {
+            id: ClassId::from(id as u16),
+            name: (*name).into(),
+            data_table: "DT_Test".into(),
+        });
+        state.send_tables.push(SendTable {
+            name: "DT_Test".into(),
+            needs_decoder: false,
+            flattened_props: Vec::new(),
+        });
+    }
+    state
+}
+
+pub fn entity(
+    index: u32,
+    class: u16,
+    update_type: UpdateType,
+    props: Vec<(SendPropIdentifier, SendPropValue)>,
+) -> PacketEntity {
+    PacketEntity {
+        server_class: ClassId::from(class),
+        entity_index: EntityId::from(index),
+        props: props
+            .into_iter()
+            .enumerate()
+            .map(|(index, (identifier, value))| SendProp {
+                index: index as u32,
+                identifier,
+                value,
+            })
+            .collect(),
+        in_pvs: true,
+        update_type,
+        serial_number: 1,
+        delay: None,
+        delta: None,
+        baseline_index: 0,
+    }
+}
+
+/// a bot connected as `user_id` from `join_tick` on, in the entity after its key
+pub fn profile(key: u32, user_id: u16, join_tick: u32) -> Profile {
+    let player = PlayerInfo {
+        name: format!("bot{key}"),
+        user_id: user_id.into(),
+        steam_id: "BOT".into(),
+        ..PlayerInfo::default()
+    };
+    Profile::new(player, key, key + 1, join_tick)
+}
+
+/// a living soldier on `team` standing at `x`, `y` and looking along `pitch`
+/// and `yaw`, as traced at `tick`
+pub fn snapshot(
+    tick: u32,
+    key: u32,
+    user_id: u16,
+    team: Team,
+    [x, y]: [f32; 2],
+    [pitch, yaw]: [f32; 2],
+) -> WithTick<Snapshot> {
+    let mut player = Player::default();
+    (player.class, player.team) = (Class::Soldier, team);
+    (player.pitch_angle, player.view_angle) = (pitch, yaw);
+    let mut inner = Snapshot::new(&player, &PlayerProps::new(), &Roster::new());
+    inner.user_id = Some(user_id);
+    inner.roster_key = Some(key);
+    (inner.position_x, inner.position_y) = (x, y);
+    WithTick { inner, tick }
+}
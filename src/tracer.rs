@@ -1,11 +1,19 @@
+use crate::columns::{ClassName, Column, LifeState, TeamName};
 use crate::errors::{Error, Result};
 
+use crate::pauses::Pauses;
+use crate::player::{cond, PlayerExtras, PlayerProps, AMMO_METAL, AMMO_PRIMARY, AMMO_SECONDARY};
+use crate::projectiles::Projectiles;
 use crate::serialize::to_polars;
+use crate::server::Server;
+use crate::sounds::Sounds;
+use crate::steamid::SteamId;
+use crate::usercmd::UserCommand;
 use bitbuffer::BitRead;
-use itertools::Itertools;
 use polars::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_arrow::schema::TracingOptions;
+use std::collections::{HashMap, HashSet};
 use tf_demo_parser::demo::data::userinfo::PlayerInfo;
 use tf_demo_parser::demo::data::userinfo::UserInfo;
 use tf_demo_parser::demo::gameevent_gen::PlayerHurtEvent;
@@ -13,10 +21,9 @@ use tf_demo_parser::demo::gamevent::GameEvent;
 use tf_demo_parser::demo::header::Header;
 use tf_demo_parser::demo::message::gameevent::GameEventMessage;
 use tf_demo_parser::demo::message::Message;
+use tf_demo_parser::demo::packet::consolecmd::ConsoleCmdPacket;
 use tf_demo_parser::demo::packet::Packet;
-use tf_demo_parser::demo::parser::gamestateanalyser::{
-    Class, GameStateAnalyser, Player, PlayerState, Team, UserId, World,
-};
+use tf_demo_parser::demo::parser::gamestateanalyser::{GameStateAnalyser, Player, UserId, World};
 use tf_demo_parser::demo::parser::handler::BorrowMessageHandler;
 use tf_demo_parser::demo::parser::{DemoHandler, MessageHandler, NullHandler, RawPacketStream};
 use tf_demo_parser::{Demo, MessageType};
@@ -57,11 +64,41 @@ impl<'s, 'h> Iterator for PacketStream<'s, 'h> {
     }
 }
 
+/// the low bits of an entity handle are the entity index, the rest is a serial number
+const HANDLE_INDEX_MASK: i64 = (1 << 11) - 1;
+
+/// resolve an entity handle prop to the entity index it refers to, if any
+pub fn handle_to_index(handle: i64) -> Option<u32> {
+    match handle & HANDLE_INDEX_MASK {
+        HANDLE_INDEX_MASK => None,
+        index => Some(index as u32),
+    }
+}
+
+/// entity index of a player, known once their userinfo entry arrives
+pub fn player_entity(player: &Player) -> Option<u32> {
+    player.info.as_ref().map(|info| u32::from(info.entity_id))
+}
+
+/// One connection of a player. A user id can be handed out again after a
+/// disconnect and bots come and go, so entries are keyed on the steam id,
+/// user id and entity index together and `key` is what other tables refer
+/// to.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Profile {
+    pub key: u32,
+    pub entity: u32,
+    pub join_tick: u32,
+    /// `None` if they were still connected when the demo ended
+    pub leave_tick: Option<u32>,
     pub name: String,
     pub user_id: UserId,
     pub steam_id: String,
+    pub steam_id64: Option<u64>,
+    pub steam_id3: Option<String>,
+    /// legacy `STEAM_0:X:Y`
+    pub steam_id2: Option<String>,
+    pub is_bot: bool,
     pub friends_id: u32,
     pub is_fake_player: bool,
     pub is_hl_tv: bool,
@@ -71,12 +108,21 @@ pub struct Profile {
     pub more_extra: bool,
 }
 
-impl From<PlayerInfo> for Profile {
-    fn from(player: PlayerInfo) -> Self {
+impl Profile {
+    pub fn new(player: PlayerInfo, key: u32, entity: u32, join_tick: u32) -> Self {
+        let steam_id = SteamId::parse(&player.steam_id);
         Self {
+            key,
+            entity,
+            join_tick,
+            leave_tick: None,
             friends_id: player.friends_id,
             user_id: player.user_id,
             name: player.name,
+            steam_id64: steam_id.map(|id| id.steam_id64()),
+            steam_id3: steam_id.map(|id| id.steam_id3()),
+            steam_id2: steam_id.map(|id| id.steam_id2()),
+            is_bot: player.is_fake_player != 0 || player.steam_id == "BOT",
             steam_id: player.steam_id,
             is_fake_player: player.is_fake_player != 0,
             is_hl_tv: player.is_hl_tv != 0,
@@ -88,62 +134,210 @@ impl From<PlayerInfo> for Profile {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Snapshot {
-    pub user_id: Option<u16>,
-    pub position_x: f32,
-    pub position_y: f32,
-    pub position_z: f32,
-    pub health: u16,
-    pub max_health: u16,
-    pub class: String,
-    pub team: String,
-    pub view_angle: f32,
-    pub pitch_angle: f32,
-    pub state: String,
-    pub charge: u8,
-    pub in_pvs: bool,
-    pub simtime: u16,
-}
-
-impl From<Player> for Snapshot {
-    fn from(value: Player) -> Self {
+/// Define a row type along with a columnar store for it, so large tables can
+/// be filled as we trace and turned into a `DataFrame` without serialising
+/// each row. Columns come out in field order, followed by `tick`.
+macro_rules! columnar {
+    (
+        $(#[$row_meta:meta])*
+        pub struct $row:ident / $columns:ident / $view:ident {
+            $($(#[$field_meta:meta])* pub $field:ident: $ty:ty,)*
+        }
+    ) => {
+        $(#[$row_meta])*
+        pub struct $row {
+            $($(#[$field_meta])* pub $field: $ty,)*
+        }
+
+        #[derive(Default)]
+        pub struct $columns {
+            tick: Vec<u32>,
+            $($field: Vec<$ty>,)*
+        }
+
+        /// A row borrowed from the columns, for reading without cloning it.
+        #[derive(Clone, Copy)]
+        pub struct $view<'a> {
+            $(pub $field: &'a $ty,)*
+        }
+
+        impl $columns {
+            pub fn push(&mut self, row: WithTick<$row>) {
+                self.tick.push(row.tick);
+                $(self.$field.push(row.inner.$field);)*
+            }
+
+            pub fn len(&self) -> usize {
+                self.tick.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.tick.is_empty()
+            }
+
+            #[allow(clippy::clone_on_copy)]
+            pub fn get(&self, index: usize) -> WithTick<$row> {
+                let inner = $row {
+                    $($field: self.$field[index].clone(),)*
+                };
+                let tick = self.tick[index];
+                WithTick { inner, tick }
+            }
+
+            pub fn iter(&self) -> impl Iterator<Item = WithTick<$row>> + '_ {
+                (0..self.len()).map(|index| self.get(index))
+            }
+
+            pub fn ticks(&self) -> &[u32] {
+                &self.tick
+            }
+
+            pub fn view(&self, index: usize) -> $view<'_> {
+                $view {
+                    $($field: &self.$field[index],)*
+                }
+            }
+
+            pub fn to_polars(self) -> Result<DataFrame> {
+                let columns = vec![
+                    $(<$ty as Column>::into_series(stringify!($field), self.$field)?,)*
+                    Series::new("tick".into(), self.tick),
+                ];
+                Ok(DataFrame::new(columns)?)
+            }
+        }
+    };
+}
+
+columnar! {
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct Snapshot / States / SnapshotView {
+        pub user_id: Option<u16>,
+        /// the `key` of this player's roster entry
+        pub roster_key: Option<u32>,
+        pub position_x: f32,
+        pub position_y: f32,
+        pub position_z: f32,
+        pub health: u16,
+        pub max_health: u16,
+        pub class: ClassName,
+        pub team: TeamName,
+        pub view_angle: f32,
+        pub pitch_angle: f32,
+        pub state: LifeState,
+        pub charge: u8,
+        pub in_pvs: bool,
+        pub simtime: u16,
+        pub ubered: bool,
+        pub kritzed: bool,
+        pub mini_crit: bool,
+        pub cloaked: bool,
+        pub disguised: bool,
+        pub burning: bool,
+        pub bleeding: bool,
+        pub jarated: bool,
+        pub milked: bool,
+        pub marked_for_death: bool,
+        pub taunting: bool,
+        pub zoomed: bool,
+        pub stunned: bool,
+        pub charging: bool,
+        pub bonked: bool,
+        pub overhealed: bool,
+        pub active_weapon: Option<String>,
+        pub active_weapon_id: Option<u32>,
+        pub primary_weapon_id: Option<u32>,
+        pub secondary_weapon_id: Option<u32>,
+        pub melee_weapon_id: Option<u32>,
+        pub primary_clip: Option<i32>,
+        pub primary_ammo: Option<i32>,
+        pub secondary_clip: Option<i32>,
+        pub secondary_ammo: Option<i32>,
+        pub metal: Option<i32>,
+        pub velocity_x: f32,
+        pub velocity_y: f32,
+        pub velocity_z: f32,
+        pub on_ground: bool,
+        pub ducking: bool,
+        pub eye_height: f32,
+        pub eye_z: f32,
+        /// whether this player recorded the demo, filled in by `trace`
+        pub pov: bool,
+    }
+}
+
+impl States {
+    /// flag the rows of the roster entry that recorded the demo
+    pub fn mark_pov(&mut self, key: u32) {
+        for (pov, row_key) in self.pov.iter_mut().zip(&self.roster_key) {
+            *pov = *row_key == Some(key);
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn new(value: &Player, props: &PlayerProps, roster: &Roster) -> Self {
+        let default = PlayerExtras::default();
+        let extras = player_entity(value)
+            .and_then(|entity| props.get(entity))
+            .unwrap_or(&default);
+        let conditions = extras.conditions;
+        let active_weapon = props.weapon(extras.active_weapon);
+        let [primary, secondary, melee] = props.loadout(extras).map(|slot| props.weapon(slot));
+        let eye_height = extras.eye_height(value.class);
         Self {
             position_x: value.position.x,
             position_y: value.position.y,
             position_z: value.position.z,
             health: value.health,
             max_health: value.max_health,
-            class: match value.class {
-                Class::Scout => "scout".to_string(),
-                Class::Soldier => "soldier".to_string(),
-                Class::Pyro => "pyro".to_string(),
-                Class::Demoman => "demoman".to_string(),
-                Class::Heavy => "heavy".to_string(),
-                Class::Engineer => "engineer".to_string(),
-                Class::Medic => "medic".to_string(),
-                Class::Sniper => "sniper".to_string(),
-                Class::Spy => "spy".to_string(),
-                Class::Other => "other".to_string(),
-            },
-            team: match value.team {
-                Team::Blue => "blu".to_string(),
-                Team::Red => "red".to_string(),
-                Team::Spectator => "spectator".to_string(),
-                Team::Other => "other".to_string(),
-            },
+            class: value.class.into(),
+            team: value.team.into(),
             view_angle: value.view_angle,
             pitch_angle: value.pitch_angle,
-            state: match value.state {
-                PlayerState::Alive => "alive".to_string(),
-                PlayerState::Death => "death".to_string(),
-                PlayerState::Dying => "dying".to_string(),
-                PlayerState::Respawnable => "queue".to_string(),
-            },
-            user_id: value.info.map(|info| info.user_id.into()),
+            state: value.state.into(),
+            roster_key: value
+                .info
+                .as_ref()
+                .and_then(|info| roster.key(u32::from(info.entity_id), info.user_id.into())),
+            user_id: value.info.as_ref().map(|info| info.user_id.into()),
             charge: value.charge,
             simtime: value.simtime,
             in_pvs: value.in_pvs,
+            ubered: conditions.ubered(),
+            kritzed: conditions.kritzed(),
+            mini_crit: conditions.mini_crit(),
+            cloaked: conditions.cloaked(),
+            disguised: conditions.has(cond::DISGUISED),
+            burning: conditions.has(cond::BURNING),
+            bleeding: conditions.has(cond::BLEEDING),
+            jarated: conditions.has(cond::URINE),
+            milked: conditions.has(cond::MAD_MILK),
+            marked_for_death: conditions.marked_for_death(),
+            taunting: conditions.has(cond::TAUNTING),
+            zoomed: conditions.has(cond::ZOOMED),
+            stunned: conditions.has(cond::STUNNED),
+            charging: conditions.has(cond::SHIELD_CHARGE),
+            bonked: conditions.has(cond::PHASE),
+            overhealed: conditions.has(cond::HEALTH_OVERHEALED),
+            active_weapon: active_weapon.map(|weapon| weapon.class.clone()),
+            active_weapon_id: active_weapon.and_then(|weapon| weapon.def_index),
+            primary_weapon_id: primary.and_then(|weapon| weapon.def_index),
+            secondary_weapon_id: secondary.and_then(|weapon| weapon.def_index),
+            melee_weapon_id: melee.and_then(|weapon| weapon.def_index),
+            primary_clip: primary.and_then(|weapon| weapon.clip),
+            primary_ammo: extras.ammo[AMMO_PRIMARY],
+            secondary_clip: secondary.and_then(|weapon| weapon.clip),
+            secondary_ammo: extras.ammo[AMMO_SECONDARY],
+            metal: extras.ammo[AMMO_METAL],
+            velocity_x: extras.velocity.x,
+            velocity_y: extras.velocity.y,
+            velocity_z: extras.velocity.z,
+            on_ground: extras.on_ground(),
+            ducking: extras.ducking(),
+            eye_height,
+            eye_z: value.position.z + eye_height,
+            pov: false,
         }
     }
 }
@@ -157,27 +351,275 @@ impl<T: Serialize + for<'de> Deserialize<'de> + Clone> WithTick<T> {
     pub fn to_polars(
         items: impl Iterator<Item = WithTick<T>>,
         tropt: Option<TracingOptions>,
-    ) -> Result<Option<DataFrame>> {
+    ) -> Result<DataFrame> {
         let (ticks, inner): (Vec<u32>, Vec<T>) =
             items.map(|WithTick { tick, inner }| (tick, inner)).unzip();
-        Ok(to_polars(inner.as_slice(), tropt)?
-            .map(|mut frame| {
-                let ticks = Series::new("tick".into(), ticks);
-                frame.with_column(ticks).map(std::mem::take)
+        let mut frame = to_polars(inner.as_slice(), tropt)?;
+        frame.with_column(Series::new("tick", ticks))?;
+        Ok(frame)
+    }
+}
+
+/// Converts ticks into seconds since the start of the demo and of the
+/// current round. The one place the tick interval lives, everything that
+/// turns ticks into time goes through it.
+#[derive(Clone)]
+pub struct Clock {
+    pub interval_per_tick: f32,
+    /// ticks of every `teamplay_round_start`, in order
+    pub round_starts: Vec<u32>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            interval_per_tick: DEFAULT_INTERVAL,
+            round_starts: Vec::new(),
+        }
+    }
+
+    pub fn time(&self, tick: u32) -> f32 {
+        tick as f32 * self.interval_per_tick
+    }
+
+    /// seconds from `from` to `to`, zero if `to` isn't after it
+    pub fn elapsed(&self, from: u32, to: u32) -> f32 {
+        self.time(to.saturating_sub(from))
+    }
+
+    /// `None` before the first round starts
+    pub fn round_time(&self, tick: u32) -> Option<f32> {
+        let index = self.round_starts.partition_point(|start| *start <= tick);
+        let start = self.round_starts.get(index.checked_sub(1)?)?;
+        Some(self.time(tick - start))
+    }
+
+    /// add `time_s` and `round_time_s` columns to a frame with a `tick` column
+    pub fn with_time(&self, mut frame: DataFrame) -> Result<DataFrame> {
+        let Ok(ticks) = frame.column("tick") else {
+            return Ok(frame);
+        };
+        let (time, round_time): (Vec<Option<f32>>, Vec<Option<f32>>) = ticks
+            .u32()?
+            .into_iter()
+            .map(|tick| {
+                let time = tick.map(|tick| self.time(tick));
+                (time, tick.and_then(|tick| self.round_time(tick)))
             })
-            .transpose()?)
+            .unzip();
+        frame.with_column(Series::new("time_s", time))?;
+        frame.with_column(Series::new("round_time_s", round_time))?;
+        Ok(frame)
+    }
+}
+
+/// Takes the interval from the header, then from `ServerInfo`, and notes
+/// every round start.
+impl MessageHandler for Clock {
+    type Output = Self;
+
+    fn does_handle(message_type: MessageType) -> bool {
+        matches!(
+            message_type,
+            MessageType::ServerInfo | MessageType::GameEvent
+        )
+    }
+
+    fn handle_header(&mut self, header: &Header) {
+        if let Some(interval) = header_interval(header) {
+            self.interval_per_tick = interval;
+        }
     }
+
+    fn handle_message(
+        &mut self,
+        message: &Message,
+        tick: tf_demo_parser::demo::data::DemoTick,
+        _parser_state: &tf_demo_parser::ParserState,
+    ) {
+        match message {
+            Message::ServerInfo(info) => self.interval_per_tick = info.interval_per_tick,
+            Message::GameEvent(GameEventMessage {
+                event: GameEvent::TeamPlayRoundStart(_),
+                ..
+            }) => self.round_starts.push(tick.into()),
+            _ => {}
+        }
+    }
+
+    fn into_output(self, _state: &tf_demo_parser::ParserState) -> Self {
+        self
+    }
+}
+
+/// what we assume until `ServerInfo` tells us otherwise, 66.67 ticks a second
+const DEFAULT_INTERVAL: f32 = 0.015;
+
+/// the tick interval implied by the header's duration, if it has one
+fn header_interval(header: &Header) -> Option<f32> {
+    (header.ticks > 0 && header.duration > 0.0).then(|| header.duration / header.ticks as f32)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NameChange {
+    pub key: u32,
+    pub user_id: u16,
+    pub steam_id: String,
+    pub name: String,
+    /// `None` for the name a player connected with
+    pub previous_name: Option<String>,
+}
+
+/// One continuous connection of a player, from the first to the last tick
+/// their userinfo entry was present. A view of `Profile` for those who only
+/// care about who was there when.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Presence {
+    pub key: u32,
+    pub user_id: u16,
+    pub steam_id: String,
+    pub name: String,
+    pub first_seen: u32,
+    pub last_seen: u32,
+    /// `None` if they were still connected when the demo ended
+    pub disconnect_tick: Option<u32>,
+}
+
+/// A string table entry we couldn't make sense of and skipped.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Warning {
+    pub table: String,
+    pub index: u32,
+    pub message: String,
+    pub raw: Vec<u8>,
 }
+
+/// the bytes of a string table entry's extra data, as far as they can be read
+fn extra_bytes(entry: &tf_demo_parser::demo::packet::stringtable::StringTableEntry) -> Vec<u8> {
+    let Some(extra) = entry.extra_data.as_ref() else {
+        return Vec::new();
+    };
+    let mut data = extra.data.clone();
+    let len = data.bits_left() / 8;
+    data.read_bytes(len)
+        .map(|bytes| bytes.into_owned())
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
 pub struct Roster {
     pub roster: Vec<Profile>,
-    user_ids: Vec<UserId>,
+    pub name_history: Vec<WithTick<NameChange>>,
+    pub warnings: Vec<WithTick<Warning>>,
+    /// userinfo table index to the key of the player connected there
+    slots: HashMap<usize, u32>,
+    tick: u32,
 }
 
 impl Roster {
     pub fn new() -> Self {
-        let roster = Vec::new();
-        let user_ids = Vec::new();
-        Self { roster, user_ids }
+        Self::default()
+    }
+
+    /// when each roster entry connected and disconnected, in roster order
+    pub fn presence(&self) -> Vec<Presence> {
+        self.roster
+            .iter()
+            .map(|profile| Presence {
+                key: profile.key,
+                user_id: profile.user_id.into(),
+                steam_id: profile.steam_id.clone(),
+                name: profile.name.clone(),
+                first_seen: profile.join_tick,
+                last_seen: profile.leave_tick.unwrap_or(self.tick),
+                disconnect_tick: profile.leave_tick,
+            })
+            .collect()
+    }
+
+    /// key of the roster entry that was connected as `user_id` at `tick`
+    pub fn key_at(&self, user_id: u16, tick: u32) -> Option<u32> {
+        let profile = self.roster.iter().rfind(|profile| {
+            u16::from(profile.user_id) == user_id
+                && profile.join_tick <= tick
+                && profile.leave_tick.is_none_or(|leave| leave >= tick)
+        })?;
+        Some(profile.key)
+    }
+
+    /// key of the roster entry connected as `user_id` on `entity`
+    pub fn key(&self, entity: u32, user_id: u16) -> Option<u32> {
+        let index = entity.checked_sub(1)? as usize;
+        self.slots
+            .get(&index)
+            .copied()
+            .filter(|&key| u16::from(self.roster[key as usize].user_id) == user_id)
+    }
+
+    /// The entry of the player that recorded a POV demo, by the entity the
+    /// local-only data tables went to or, failing that, the nick in the
+    /// header if the demo has user commands. Both `trace` and `Pov::scan`
+    /// resolve it here so they can't disagree.
+    pub fn recorder(
+        &self,
+        local_player: Option<u32>,
+        has_usercmds: bool,
+        nick: &str,
+    ) -> Option<&Profile> {
+        let mut players = self.roster.iter().filter(|profile| !profile.is_hl_tv);
+        match local_player {
+            Some(entity) => players.rfind(|profile| profile.entity == entity),
+            None if has_usercmds => players.find(|profile| profile.name == nick),
+            None => None,
+        }
+    }
+
+    fn disconnect(&mut self, index: usize) {
+        if let Some(key) = self.slots.remove(&index) {
+            self.roster[key as usize].leave_tick = Some(self.tick);
+        }
+    }
+
+    fn update(&mut self, index: usize, player: PlayerInfo) {
+        let tick = self.tick;
+        let connected = self.slots.get(&index).copied().filter(|&key| {
+            let profile = &self.roster[key as usize];
+            profile.user_id == player.user_id && profile.steam_id == player.steam_id
+        });
+        let (key, previous_name) = match connected {
+            Some(key) => {
+                let profile = &mut self.roster[key as usize];
+                let previous_name = profile.name.clone();
+                *profile = Profile::new(player, key, profile.entity, profile.join_tick);
+                (key, Some(previous_name))
+            }
+            None => {
+                self.disconnect(index);
+                let key = self.roster.len() as u32;
+                self.slots.insert(index, key);
+                let entity = index as u32 + 1;
+                self.roster.push(Profile::new(player, key, entity, tick));
+                (key, None)
+            }
+        };
+
+        let profile = &self.roster[key as usize];
+        if previous_name.as_ref() != Some(&profile.name) {
+            let inner = NameChange {
+                key,
+                user_id: profile.user_id.into(),
+                steam_id: profile.steam_id.clone(),
+                name: profile.name.clone(),
+                previous_name,
+            };
+            self.name_history.push(WithTick { inner, tick });
+        }
     }
 }
 
@@ -199,66 +641,187 @@ impl MessageHandler for Roster {
         _parser_state: &tf_demo_parser::ParserState,
     ) {
         if table == "userinfo" {
-            if let Some(UserInfo {
-                player_info: player,
-                ..
-            }) = {
-                let index = index as u16;
-                let text = entry.text.as_ref().map(AsRef::as_ref);
-                let data = entry.extra_data.as_ref().map(|extra| extra.data.clone());
-                UserInfo::parse_from_string_table(index, text, data).unwrap()
-            } {
-                if !self.user_ids.contains(&player.user_id) {
-                    self.user_ids.push(player.user_id);
-                    self.roster.push(Profile::from(player));
+            let text = entry.text.as_ref().map(AsRef::as_ref);
+            let data = entry.extra_data.as_ref().map(|extra| extra.data.clone());
+            let info = match UserInfo::parse_from_string_table(index as u16, text, data) {
+                Ok(info) => info,
+                Err(error) => {
+                    let inner = Warning {
+                        table: table.to_string(),
+                        index: index as u32,
+                        message: error.to_string(),
+                        raw: extra_bytes(entry),
+                    };
+                    let tick = self.tick;
+                    self.warnings.push(WithTick { inner, tick });
+                    return;
                 }
+            };
+            match info {
+                Some(UserInfo {
+                    player_info: player,
+                    ..
+                }) => self.update(index, player),
+                // the entry is cleared when the player leaves
+                None => self.disconnect(index),
             }
         }
     }
 
+    fn handle_packet_meta(
+        &mut self,
+        tick: tf_demo_parser::demo::data::DemoTick,
+        _meta: &tf_demo_parser::demo::packet::message::MessagePacketMeta,
+        _parser_state: &tf_demo_parser::ParserState,
+    ) {
+        self.tick = tick.into();
+    }
+
     fn into_output(self, _state: &tf_demo_parser::ParserState) -> Self {
         self
     }
 }
 
+/// A command the recording client ran from its console, a bind or a config.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConsoleCommand {
+    pub command: String,
+}
+
+impl ConsoleCommand {
+    pub fn new(packet: &ConsoleCmdPacket) -> WithTick<Self> {
+        let inner = Self {
+            command: packet.command.clone(),
+        };
+        let tick = packet.tick.into();
+        WithTick { inner, tick }
+    }
+}
+
 pub struct Tracer {
     pub integrator: GameStateAnalyser,
     pub events: Vec<WithTick<PlayerHurtEvent>>,
-    pub states: Vec<WithTick<Snapshot>>,
+    pub states: States,
     pub roster: Roster,
     pub bounds: Vec<WithTick<World>>,
-    deltas: Vec<Player>,
+    pub projectiles: Projectiles,
+    pub player_props: PlayerProps,
+    pub server: Server,
+    pub pauses: Pauses,
+    pub sounds: Sounds,
+    /// filled in by `trace`, user commands don't pass through a `MessageHandler`
+    pub usercmds: Vec<WithTick<UserCommand>>,
+    /// filled in by `trace`, the commands the recording client ran
+    pub console: Vec<WithTick<ConsoleCommand>>,
+    /// filled in by `trace`, the roster key of `Tracer::recorder`
+    pub pov_key: Option<u32>,
+    /// interval from `ServerInfo`, or the header until we see it
+    pub clock: Clock,
+    /// the tick of the last message, string table entries don't carry one
+    tick: u32,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Tracer {
     pub fn new() -> Self {
         Self {
             integrator: GameStateAnalyser::new(),
-            states: Vec::new(),
-            deltas: Vec::new(),
+            states: States::default(),
             events: Vec::new(),
             roster: Roster::new(),
             bounds: Vec::new(),
+            projectiles: Projectiles::new(),
+            player_props: PlayerProps::new(),
+            server: Server::default(),
+            pauses: Pauses::default(),
+            sounds: Sounds::default(),
+            usercmds: Vec::new(),
+            console: Vec::new(),
+            pov_key: None,
+            clock: Clock::new(),
+            tick: 0,
         }
     }
 
-    fn compute_deltas(
-        &mut self,
-        message: &tf_demo_parser::demo::message::Message,
-        tick: tf_demo_parser::demo::data::DemoTick,
+    /// the roster entry of the player that recorded a POV demo, see
+    /// `Roster::recorder`
+    pub fn recorder(&self, nick: &str) -> Option<&Profile> {
+        let local_player = self.player_props.local_player();
+        self.roster
+            .recorder(local_player, !self.usercmds.is_empty(), nick)
+    }
+
+    /// entity indices of the players a message changed, either directly,
+    /// through one of the weapons they carry or through their entries in
+    /// `CTFPlayerResource`
+    fn dirty_players(
+        &self,
+        message: &Message,
         parser_state: &tf_demo_parser::ParserState,
-    ) {
-        let prev_states = self.integrator.state.players.clone();
-        self.integrator.handle_message(message, tick, parser_state);
-        self.deltas = self
-            .integrator
-            .state
-            .players
+    ) -> HashSet<u32> {
+        let Message::PacketEntities(message) = message else {
+            return HashSet::new();
+        };
+        let mut dirty = HashSet::new();
+        for entity in &message.entities {
+            let is_resource = parser_state
+                .server_classes
+                .get(usize::from(entity.server_class))
+                .is_some_and(|class| class.name.as_str() == "CTFPlayerResource");
+            if !is_resource {
+                continue;
+            }
+            // the resource holds an array per field, indexed by player entity
+            for prop in entity.props(parser_state) {
+                let Some((table, index)) = prop.identifier.names() else {
+                    continue;
+                };
+                let tracked = matches!(
+                    table.as_str(),
+                    "m_iTeam" | "m_iMaxHealth" | "m_iPlayerClass" | "m_iChargeLevel"
+                );
+                if let Ok(index) = index.as_str().parse() {
+                    if tracked {
+                        dirty.insert(index);
+                    }
+                }
+            }
+        }
+        let updated: HashSet<u32> = message
+            .entities
             .iter()
-            .chain(prev_states.iter())
-            .unique_by(|player| player.info.as_ref().map(|info| info.user_id))
-            .cloned()
+            .map(|entity| u32::from(entity.entity_index))
+            .chain(
+                message
+                    .removed_entities
+                    .iter()
+                    .map(|&entity| u32::from(entity)),
+            )
             .collect();
+        let players = self.integrator.state.players.iter();
+        dirty.extend(players.filter_map(player_entity).filter(|entity| {
+            updated.contains(entity)
+                || self
+                    .player_props
+                    .get(*entity)
+                    .is_some_and(|extras| updated.iter().any(|&weapon| extras.carries(weapon)))
+        }));
+        dirty
+    }
+
+    /// record the state of every connected player in `dirty` at `tick`
+    fn snapshot(&mut self, dirty: &HashSet<u32>, tick: u32) {
+        for player in &self.integrator.state.players {
+            if player_entity(player).is_some_and(|entity| dirty.contains(&entity)) {
+                let inner = Snapshot::new(player, &self.player_props, &self.roster);
+                self.states.push(WithTick { tick, inner });
+            }
+        }
     }
 }
 
@@ -271,11 +834,17 @@ impl MessageHandler for Tracer {
             MessageType::GameEvent
                 | MessageType::CreateStringTable
                 | MessageType::UpdateStringTable
+                | MessageType::PacketEntities
+                | MessageType::ServerInfo
+                | MessageType::SetConVar
+                | MessageType::SetPause
+                | MessageType::ParseSounds
         ) || GameStateAnalyser::does_handle(message_type)
     }
 
     fn handle_header(&mut self, header: &tf_demo_parser::demo::header::Header) {
         self.integrator.handle_header(header);
+        self.clock.handle_header(header);
     }
 
     fn handle_message(
@@ -295,6 +864,7 @@ impl MessageHandler for Tracer {
             let tick = tick.into();
             self.bounds.push(WithTick { inner, tick });
         }
+        self.clock.handle_message(message, tick, parser_state);
         if let Message::GameEvent(GameEventMessage {
             event: GameEvent::PlayerHurt(event),
             ..
@@ -304,14 +874,21 @@ impl MessageHandler for Tracer {
             let tick = tick.into();
             self.events.push(WithTick { tick, inner });
         }
-        self.compute_deltas(message, tick, parser_state);
-        for player in std::mem::take(&mut self.deltas).into_iter() {
-            if player.info.is_some() {
-                let inner = player.clone().into();
-                let tick = tick.into();
-                self.states.push(WithTick { tick, inner });
-            }
-        }
+        self.server.handle_message(message, tick.into());
+        self.pauses.handle_message(message, tick.into());
+        self.sounds.handle_message(message, tick.into());
+        self.player_props
+            .handle_message(message, tick.into(), &self.clock, parser_state);
+        self.projectiles.handle_message(
+            message,
+            tick.into(),
+            &self.clock,
+            parser_state,
+            &self.integrator.state.players,
+        );
+        self.tick = tick.into();
+        let dirty = self.dirty_players(message, parser_state);
+        self.snapshot(&dirty, tick.into());
     }
 
     fn handle_string_entry(
@@ -325,6 +902,12 @@ impl MessageHandler for Tracer {
             .handle_string_entry(table, index, entry, parser_state);
         self.roster
             .handle_string_entry(table, index, entry, parser_state);
+        self.sounds.handle_string_entry(table, index, entry);
+        if table == "userinfo" {
+            // a new name or user id is a new state too
+            let dirty = HashSet::from([index as u32 + 1]);
+            self.snapshot(&dirty, self.tick);
+        }
     }
 
     fn handle_data_tables(
@@ -344,15 +927,135 @@ impl MessageHandler for Tracer {
         parser_state: &tf_demo_parser::ParserState,
     ) {
         self.integrator.handle_packet_meta(tick, meta, parser_state);
+        self.roster.handle_packet_meta(tick, meta, parser_state);
+        self.pauses.handle_tick(tick.into(), &self.clock);
     }
 
-    fn into_output(self, _state: &tf_demo_parser::ParserState) -> Self::Output {
+    fn into_output(mut self, state: &tf_demo_parser::ParserState) -> Self::Output {
+        self.roster = self.roster.into_output(state);
+        self.pauses.finish();
         self
     }
 }
 
 impl BorrowMessageHandler for Tracer {
     fn borrow_output(&self, _state: &tf_demo_parser::ParserState) -> &Self::Output {
-        &self
+        self
+    }
+}
+
+/// Run `analyser` over a demo, showing every packet to `visit` first so the
+/// ones never handed to a `MessageHandler` can be collected too.
+pub fn scan<A: MessageHandler>(
+    demo: Demo,
+    analyser: A,
+    mut visit: impl FnMut(&Packet) -> Result<()>,
+) -> Result<(Header, A::Output)> {
+    let mut stream = demo.get_stream();
+    let header = Header::read(&mut stream)?;
+    let mut handler = DemoHandler::with_analyser(analyser);
+    handler.handle_header(&header);
+    let mut packets = RawPacketStream::new(stream);
+    while let Some(packet) = packets.next(&handler.state_handler)? {
+        visit(&packet)?;
+        handler.handle_packet(packet)?;
+    }
+    Ok((header, handler.into_output()))
+}
+
+/// Run a `Tracer` over a demo, additionally collecting the packets that are
+/// never handed to a `MessageHandler`.
+pub fn trace(demo: Demo) -> Result<(Header, Tracer)> {
+    let (mut usercmds, mut console) = (Vec::new(), Vec::new());
+    let (header, mut tracer) = scan(demo, Tracer::new(), |packet| {
+        match packet {
+            Packet::UserCmd(packet) => usercmds.push(UserCommand::decode(packet)?),
+            Packet::ConsoleCmd(packet) => console.push(ConsoleCommand::new(packet)),
+            _ => {}
+        }
+        Ok(())
+    })?;
+    tracer.usercmds = usercmds;
+    tracer.console = console;
+    tracer.pov_key = tracer.recorder(&header.nick).map(|profile| profile.key);
+    if let Some(key) = tracer.pov_key {
+        tracer.states.mark_pov(key);
+    }
+    Ok((header, tracer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitbuffer::{BitReadBuffer, BitReadStream, LittleEndian};
+    use tf_demo_parser::demo::packet::stringtable::{ExtraData, StringTableEntry};
+    use tf_demo_parser::ParserState;
+
+    fn userinfo(name: &str, user_id: u16) -> StringTableEntry<'static> {
+        let player_info = PlayerInfo {
+            name: name.into(),
+            user_id: user_id.into(),
+            steam_id: "[U:1:2]".into(),
+            ..Default::default()
+        };
+        let info = UserInfo {
+            player_info,
+            ..Default::default()
+        };
+        info.encode_to_string_table().unwrap()
+    }
+
+    #[test]
+    fn roster_warns_on_unreadable_userinfo() {
+        let state = ParserState::new(24, |_| true, false);
+        let mut roster = Roster::new();
+        roster.tick = 10;
+        roster.handle_string_entry("userinfo", 0, &userinfo("first", 3), &state);
+        roster.tick = 20;
+        roster.handle_string_entry("userinfo", 0, &userinfo("second", 3), &state);
+        // too short to hold a player info
+        let data = BitReadBuffer::new_owned(vec![1, 2, 3], LittleEndian);
+        let truncated = StringTableEntry {
+            text: None,
+            extra_data: Some(ExtraData::new(BitReadStream::new(data))),
+        };
+        roster.tick = 30;
+        roster.handle_string_entry("userinfo", 1, &truncated, &state);
+
+        assert_eq!(roster.roster.len(), 1);
+        assert_eq!(roster.roster[0].name, "second");
+        let names: Vec<_> = roster
+            .name_history
+            .iter()
+            .map(|change| (change.tick, change.inner.previous_name.as_deref()))
+            .collect();
+        assert_eq!(names, vec![(10, None), (20, Some("first"))]);
+        assert_eq!(roster.warnings.len(), 1);
+        let warning = &roster.warnings[0];
+        assert_eq!((warning.tick, warning.inner.index), (30, 1));
+        assert_eq!(warning.inner.table, "userinfo");
+        assert_eq!(warning.inner.raw, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn console_table() {
+        let packet = ConsoleCmdPacket {
+            tick: 200u32.into(),
+            command: "echo flag taken".into(),
+        };
+        let command = ConsoleCommand::new(&packet);
+        assert_eq!(command.tick, 200);
+        let tropt = crate::schema::tracing_options();
+        let frame = WithTick::to_polars(std::iter::once(command), Some(tropt)).unwrap();
+        let frame = crate::schema::finish("console", frame, &Clock::new()).unwrap();
+        assert_eq!(frame.height(), 1);
+        let command = frame.column("command").unwrap().str().unwrap().get(0);
+        assert_eq!(command, Some("echo flag taken"));
+        assert_eq!(
+            frame.column("tick").unwrap().u32().unwrap().get(0),
+            Some(200)
+        );
+        let time = frame.column("time_s").unwrap().f32().unwrap().get(0);
+        assert_eq!(time, Some(200.0 * DEFAULT_INTERVAL));
     }
 }
@@ -0,0 +1,166 @@
+use bitbuffer::{BitReadBuffer, BitReadStream, BitWrite, BitWriteStream, LittleEndian};
+use serde::{Deserialize, Serialize};
+use tf_demo_parser::demo::packet::usercmd::{UserCmdPacket, WeaponSelect};
+
+use crate::errors::Result;
+use crate::tracer::WithTick;
+
+/// `IN_*` button bits, see `in_buttons.h`
+pub mod button {
+    pub const ATTACK: u32 = 1 << 0;
+    pub const JUMP: u32 = 1 << 1;
+    pub const DUCK: u32 = 1 << 2;
+    pub const FORWARD: u32 = 1 << 3;
+    pub const BACK: u32 = 1 << 4;
+    pub const USE: u32 = 1 << 5;
+    pub const MOVELEFT: u32 = 1 << 9;
+    pub const MOVERIGHT: u32 = 1 << 10;
+    pub const ATTACK2: u32 = 1 << 11;
+    pub const RELOAD: u32 = 1 << 13;
+    pub const SCORE: u32 = 1 << 16;
+    pub const ATTACK3: u32 = 1 << 25;
+}
+
+/// The weapon index and subtype of a `WeaponSelect`, whose fields are
+/// private. They're read back from its wire format, an 11 bit index followed
+/// by an optional 6 bit subtype.
+fn weapon_select(select: &WeaponSelect) -> Result<(u32, u8)> {
+    let mut data = Vec::new();
+    select.write(&mut BitWriteStream::new(&mut data, LittleEndian))?;
+    let mut stream = BitReadStream::new(BitReadBuffer::new(&data, LittleEndian));
+    let index: u32 = stream.read_int(11)?;
+    let subtype: Option<u8> = stream.read_sized(6)?;
+    Ok((index, subtype.unwrap_or_default()))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UserCommand {
+    pub command_number: u32,
+    pub tick_count: u32,
+    pub sequence_out: u32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub roll: f32,
+    pub forward_move: f32,
+    pub side_move: f32,
+    pub up_move: f32,
+    pub buttons: u32,
+    pub impulse: u8,
+    pub weapon_select: u32,
+    pub weapon_subtype: u8,
+    pub mouse_dx: i16,
+    pub mouse_dy: i16,
+    pub in_attack: bool,
+    pub in_attack2: bool,
+    pub in_attack3: bool,
+    pub in_jump: bool,
+    pub in_duck: bool,
+    pub in_forward: bool,
+    pub in_back: bool,
+    pub in_moveleft: bool,
+    pub in_moveright: bool,
+    pub in_use: bool,
+    pub in_reload: bool,
+    pub in_score: bool,
+}
+
+impl UserCommand {
+    pub fn decode(packet: &UserCmdPacket) -> Result<WithTick<Self>> {
+        // demos encode user commands against an empty command, so absent
+        // fields are zero
+        let cmd = &packet.cmd;
+        let [pitch, yaw, roll] = cmd.view_angles.map(Option::unwrap_or_default);
+        let [forward_move, side_move, up_move] = cmd.movement.map(Option::unwrap_or_default);
+        let buttons = cmd.buttons.unwrap_or_default();
+        let pressed = |button: u32| buttons & button != 0;
+        let (weapon_select, weapon_subtype) = match &cmd.weapon_select {
+            Some(select) => weapon_select(select)?,
+            None => (0, 0),
+        };
+        let inner = Self {
+            command_number: cmd.command_number.unwrap_or_default(),
+            tick_count: cmd.tick_count.unwrap_or_default(),
+            sequence_out: packet.sequence_out,
+            pitch,
+            yaw,
+            roll,
+            forward_move,
+            side_move,
+            up_move,
+            buttons,
+            impulse: cmd.impulse.unwrap_or_default(),
+            weapon_select,
+            weapon_subtype,
+            mouse_dx: cmd.mouse_dx.unwrap_or_default() as i16,
+            mouse_dy: cmd.mouse_dy.unwrap_or_default() as i16,
+            in_attack: pressed(button::ATTACK),
+            in_attack2: pressed(button::ATTACK2),
+            in_attack3: pressed(button::ATTACK3),
+            in_jump: pressed(button::JUMP),
+            in_duck: pressed(button::DUCK),
+            in_forward: pressed(button::FORWARD),
+            in_back: pressed(button::BACK),
+            in_moveleft: pressed(button::MOVELEFT),
+            in_moveright: pressed(button::MOVERIGHT),
+            in_use: pressed(button::USE),
+            in_reload: pressed(button::RELOAD),
+            in_score: pressed(button::SCORE),
+        };
+        let tick = packet.tick.into();
+        Ok(WithTick { inner, tick })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitbuffer::BitRead;
+    use tf_demo_parser::demo::packet::usercmd::UserCmd;
+
+    fn packet(weapon_select: Option<WeaponSelect>) -> UserCmdPacket {
+        UserCmdPacket {
+            tick: 120u32.into(),
+            sequence_out: 9,
+            cmd: UserCmd {
+                command_number: Some(42),
+                tick_count: None,
+                view_angles: [Some(-10.0), Some(90.0), None],
+                movement: [Some(450.0), None, None],
+                buttons: Some(button::ATTACK | button::JUMP),
+                impulse: None,
+                weapon_select,
+                mouse_dx: Some(u16::MAX),
+                mouse_dy: None,
+            },
+        }
+    }
+
+    #[test]
+    fn absent_fields_are_zero() {
+        let cmd = UserCommand::decode(&packet(None)).unwrap();
+        assert_eq!(cmd.tick, 120);
+        let cmd = cmd.inner;
+        assert_eq!(
+            (cmd.command_number, cmd.tick_count, cmd.sequence_out),
+            (42, 0, 9)
+        );
+        assert_eq!((cmd.pitch, cmd.yaw, cmd.roll), (-10.0, 90.0, 0.0));
+        assert_eq!((cmd.forward_move, cmd.side_move), (450.0, 0.0));
+        assert!(cmd.in_attack && cmd.in_jump && !cmd.in_duck);
+        assert_eq!((cmd.weapon_select, cmd.weapon_subtype), (0, 0));
+        assert_eq!((cmd.mouse_dx, cmd.mouse_dy), (-1, 0));
+    }
+
+    #[test]
+    fn weapon_select() {
+        let mut data = Vec::new();
+        let mut writer = BitWriteStream::new(&mut data, LittleEndian);
+        writer.write_int(17u16, 11).unwrap();
+        writer.write_bool(true).unwrap();
+        writer.write_int(2u8, 6).unwrap();
+        let mut stream = BitReadStream::new(BitReadBuffer::new_owned(data, LittleEndian));
+        let select = WeaponSelect::read(&mut stream).unwrap();
+        let cmd = UserCommand::decode(&packet(Some(select))).unwrap().inner;
+        assert_eq!((cmd.weapon_select, cmd.weapon_subtype), (17, 2));
+    }
+}